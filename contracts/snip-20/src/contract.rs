@@ -333,11 +333,17 @@ pub fn viewing_keys_queries<S: Storage, A: Api, Q: Querier>(
 
         let expected_key = read_viewing_key(&deps.storage, &canonical_addr);
 
-        if expected_key.is_none() {
-            // Checking the key will take significant time. We don't want to exit immediately if it isn't set
-            // in a way which will allow to time the command and determine if a viewing key doesn't exist
-            key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
-        } else if key.check_viewing_key(expected_key.unwrap().as_slice()) {
+        let is_valid_key = match expected_key {
+            Some(expected_key) => key.check_viewing_key(expected_key.as_slice()),
+            None => {
+                // Checking the key will take significant time. We don't want to exit immediately if it isn't set
+                // in a way which will allow to time the command and determine if a viewing key doesn't exist
+                key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+                false
+            }
+        };
+
+        if is_valid_key {
             return match msg {
                 // Base
                 QueryMsg::Balance { address, .. } => query_balance(deps, &address),
@@ -666,7 +672,7 @@ pub fn try_create_key<S: Storage, A: Api, Q: Querier>(
     let constants = ReadonlyConfig::from_storage(&deps.storage).constants()?;
     let prng_seed = constants.prng_seed;
 
-    let key = ViewingKey::new(&env, &prng_seed, (&entropy).as_ref());
+    let key = ViewingKey::new(&env, &prng_seed, entropy.as_ref());
 
     let message_sender = deps.api.canonical_address(&env.message.sender)?;
     write_viewing_key(&mut deps.storage, &message_sender, &key);
@@ -1735,7 +1741,7 @@ fn is_valid_symbol(symbol: &str) -> bool {
     let len = symbol.len();
     let len_is_valid = (3..=6).contains(&len);
 
-    len_is_valid && symbol.bytes().all(|byte| (b'A'..=b'Z').contains(&byte))
+    len_is_valid && symbol.bytes().all(|byte| byte.is_ascii_uppercase())
 }
 
 // pub fn migrate<S: Storage, A: Api, Q: Querier>(
@@ -1887,7 +1893,7 @@ mod tests {
             | HandleAnswer::SetMinters { status }
             | HandleAnswer::AddMinters { status }
             | HandleAnswer::RemoveMinters { status } => {
-                matches!(status, ResponseStatus::Success { .. })
+                matches!(status, ResponseStatus::Success)
             }
             _ => panic!(
                 "HandleAnswer not supported for success extraction: {:?}",
@@ -1918,7 +1924,7 @@ mod tests {
             constants.prng_seed,
             sha_256("lolz fun yay".to_owned().as_bytes())
         );
-        assert_eq!(constants.total_supply_is_public, false);
+        assert!(!constants.total_supply_is_public);
     }
 
     #[test]
@@ -1948,18 +1954,18 @@ mod tests {
             constants.prng_seed,
             sha_256("lolz fun yay".to_owned().as_bytes())
         );
-        assert_eq!(constants.total_supply_is_public, false);
-        assert_eq!(constants.deposit_is_enabled, true);
-        assert_eq!(constants.redeem_is_enabled, true);
-        assert_eq!(constants.mint_is_enabled, true);
-        assert_eq!(constants.burn_is_enabled, true);
+        assert!(!constants.total_supply_is_public);
+        assert!(constants.deposit_is_enabled);
+        assert!(constants.redeem_is_enabled);
+        assert!(constants.mint_is_enabled);
+        assert!(constants.burn_is_enabled);
     }
 
     #[test]
     fn test_total_supply_overflow() {
         let (init_result, _deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("lebron".to_string()),
-            amount: Uint128(u128::max_value()),
+            amount: Uint128(u128::MAX),
         }]);
         assert!(
             init_result.is_ok(),
@@ -1970,7 +1976,7 @@ mod tests {
         let (init_result, _deps) = init_helper(vec![
             InitialBalance {
                 address: HumanAddr("lebron".to_string()),
-                amount: Uint128(u128::max_value()),
+                amount: Uint128(u128::MAX),
             },
             InitialBalance {
                 address: HumanAddr("giannis".to_string()),
@@ -2915,7 +2921,7 @@ mod tests {
         let contract_status = ReadonlyConfig::from_storage(&deps.storage).contract_status();
         assert!(matches!(
             contract_status,
-            ContractStatusLevel::StopAll { .. }
+            ContractStatusLevel::StopAll
         ));
     }
 
@@ -3676,7 +3682,7 @@ mod tests {
             name: init_name.clone(),
             admin: Some(init_admin.clone()),
             symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
+            decimals: init_decimals,
             initial_balances: Some(vec![InitialBalance {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
@@ -3742,7 +3748,7 @@ mod tests {
             name: init_name.clone(),
             admin: Some(init_admin.clone()),
             symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
+            decimals: init_decimals,
             initial_balances: Some(vec![InitialBalance {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
@@ -3773,11 +3779,11 @@ mod tests {
                 mint_enabled,
                 burn_enabled,
             } => {
-                assert_eq!(public_total_supply, true);
-                assert_eq!(deposit_enabled, false);
-                assert_eq!(redeem_enabled, false);
-                assert_eq!(mint_enabled, true);
-                assert_eq!(burn_enabled, false);
+                assert!(public_total_supply);
+                assert!(!deposit_enabled);
+                assert!(!redeem_enabled);
+                assert!(mint_enabled);
+                assert!(!burn_enabled);
             }
             _ => panic!("unexpected"),
         }
@@ -3811,7 +3817,7 @@ mod tests {
             name: init_name.clone(),
             admin: Some(init_admin.clone()),
             symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
+            decimals: init_decimals,
             initial_balances: Some(vec![InitialBalance {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
@@ -3868,7 +3874,7 @@ mod tests {
             name: init_name.clone(),
             admin: Some(init_admin.clone()),
             symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
+            decimals: init_decimals,
             initial_balances: Some(vec![InitialBalance {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
@@ -3925,7 +3931,7 @@ mod tests {
             name: init_name.clone(),
             admin: Some(init_admin.clone()),
             symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
+            decimals: init_decimals,
             initial_balances: Some(vec![InitialBalance {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
@@ -3970,7 +3976,7 @@ mod tests {
             name: init_name.clone(),
             admin: Some(init_admin.clone()),
             symbol: init_symbol.clone(),
-            decimals: init_decimals.clone(),
+            decimals: init_decimals,
             initial_balances: Some(vec![InitialBalance {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
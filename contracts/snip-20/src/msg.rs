@@ -485,7 +485,7 @@ pub fn space_pad(block_size: usize, message: &mut Vec<u8>) -> &mut Vec<u8> {
 
     let missing = block_size - surplus;
     message.reserve(missing);
-    message.extend(std::iter::repeat(b' ').take(missing));
+    message.resize(len + missing, b' ');
     message
 }
 
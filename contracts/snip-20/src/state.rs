@@ -62,7 +62,7 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfig<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyConfigImpl<ReadonlyPrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyConfigImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
         ReadonlyConfigImpl(&self.storage)
     }
 
@@ -92,7 +92,7 @@ fn ser_bin_data<T: Serialize>(obj: &T) -> StdResult<Vec<u8>> {
 }
 
 fn deser_bin_data<T: DeserializeOwned>(data: &[u8]) -> StdResult<T> {
-    bincode2::deserialize::<T>(&data).map_err(|e| StdError::serialize_err(type_name::<T>(), e))
+    bincode2::deserialize::<T>(data).map_err(|e| StdError::serialize_err(type_name::<T>(), e))
 }
 
 fn set_bin_data<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], data: &T) -> StdResult<()> {
@@ -122,7 +122,7 @@ impl<'a, S: Storage> Config<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyConfigImpl<PrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyConfigImpl<'_, PrefixedStorage<'_, S>> {
         ReadonlyConfigImpl(&self.storage)
     }
 
@@ -245,7 +245,7 @@ impl<'a, S: ReadonlyStorage> ReadonlyBalances<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyBalancesImpl<ReadonlyPrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyBalancesImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
         ReadonlyBalancesImpl(&self.storage)
     }
 
@@ -265,7 +265,7 @@ impl<'a, S: Storage> Balances<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyBalancesImpl<PrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyBalancesImpl<'_, PrefixedStorage<'_, S>> {
         ReadonlyBalancesImpl(&self.storage)
     }
 
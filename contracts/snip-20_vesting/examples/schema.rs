@@ -3,7 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use snip_20_vesting::msg::{HandleMsg, InitMsg, QueryMsg, VestingRoundResponse, ContractOwnerResponse};
+use snip_20_vesting::msg::{HandleMsg, InitMsg, QueryMsg, VestingRoundResponse, ContractOwnerResponse, ClaimHistoryResponse, EscrowBalanceResponse, ReceiveMsg, ContractViewingKeyResponse, RoundFundingStatusResponse, HandleAnswer, ContractStatusResponse, EmergencyWithdrawalsResponse, UnlockScheduleResponse, AllocationsResponse, VestingRoundsResponse, RoundStatsResponse, ReconcileResponse, ExportPositionsResponse, StakingStatusResponse};
 use snip_20_vesting::state::{VestingRoundState};
 
 fn main() {
@@ -15,7 +15,22 @@ fn main() {
     export_schema(&schema_for!(InitMsg), &out_dir);
     export_schema(&schema_for!(HandleMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
     export_schema(&schema_for!(ContractOwnerResponse), &out_dir);
     export_schema(&schema_for!(VestingRoundResponse), &out_dir);
     export_schema(&schema_for!(VestingRoundState), &out_dir);
+    export_schema(&schema_for!(ClaimHistoryResponse), &out_dir);
+    export_schema(&schema_for!(EscrowBalanceResponse), &out_dir);
+    export_schema(&schema_for!(ContractViewingKeyResponse), &out_dir);
+    export_schema(&schema_for!(RoundFundingStatusResponse), &out_dir);
+    export_schema(&schema_for!(HandleAnswer), &out_dir);
+    export_schema(&schema_for!(ContractStatusResponse), &out_dir);
+    export_schema(&schema_for!(EmergencyWithdrawalsResponse), &out_dir);
+    export_schema(&schema_for!(UnlockScheduleResponse), &out_dir);
+    export_schema(&schema_for!(AllocationsResponse), &out_dir);
+    export_schema(&schema_for!(VestingRoundsResponse), &out_dir);
+    export_schema(&schema_for!(RoundStatsResponse), &out_dir);
+    export_schema(&schema_for!(ReconcileResponse), &out_dir);
+    export_schema(&schema_for!(ExportPositionsResponse), &out_dir);
+    export_schema(&schema_for!(StakingStatusResponse), &out_dir);
 }
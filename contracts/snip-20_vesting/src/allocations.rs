@@ -0,0 +1,135 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    Api, CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{Bucket, PrefixedStorage, ReadonlyBucket, ReadonlyPrefixedStorage};
+
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+use crate::utils::page_bounds;
+
+const PREFIX_ALLOCATIONS: &[u8] = b"allocations";
+const PREFIX_ALLOCATION_INDEX: &[u8] = b"allocation_index";
+
+/// Grant written on-chain by the admin of an explicit round, with the same fields as a
+/// merkle leaf
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct Allocation {
+    pub address: HumanAddr,
+    /// Index of the grant among the address' grants in the stage
+    #[serde(default)]
+    pub grant: u64,
+    pub amount: Uint128,
+    pub tge: Uint128,
+    pub start_at: u64,
+    pub cliff: u64,
+    pub duration: u64,
+}
+
+// Stored types:
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct StoredAllocation {
+    pub amount: Uint128,
+    pub tge: Uint128,
+    pub start_at: u64,
+    pub cliff: u64,
+    pub duration: u64,
+}
+
+impl StoredAllocation {
+    fn into_humanized(self, address: HumanAddr, grant: u64) -> Allocation {
+        Allocation {
+            address,
+            grant,
+            amount: self.amount,
+            tge: self.tge,
+            start_at: self.start_at,
+            cliff: self.cliff,
+            duration: self.duration,
+        }
+    }
+}
+
+// Storage functions:
+
+fn allocation_key(user: &CanonicalAddr, grant: u64) -> Vec<u8> {
+    let mut key = user.as_slice().to_vec();
+    key.extend_from_slice(&grant.to_be_bytes());
+    key
+}
+
+pub fn add_allocation<S: Storage>(
+    store: &mut S,
+    stage: u128,
+    user: &CanonicalAddr,
+    grant: u64,
+    allocation: &StoredAllocation,
+) -> StdResult<()> {
+    if read_allocation(store, stage, user, grant)?.is_some() {
+        return Err(StdError::generic_err(
+            "An allocation already exists for this grant!",
+        ));
+    }
+
+    Bucket::<S, StoredAllocation>::multilevel(&[PREFIX_ALLOCATIONS, &stage.to_be_bytes()], store)
+        .save(&allocation_key(user, grant), allocation)?;
+
+    // Keep the insertion order so auditors can page through the allocations
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_ALLOCATION_INDEX, &stage.to_be_bytes()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(&(user.clone(), grant))
+}
+
+pub fn read_allocation<S: ReadonlyStorage>(
+    storage: &S,
+    stage: u128,
+    user: &CanonicalAddr,
+    grant: u64,
+) -> StdResult<Option<StoredAllocation>> {
+    ReadonlyBucket::<S, StoredAllocation>::multilevel(
+        &[PREFIX_ALLOCATIONS, &stage.to_be_bytes()],
+        storage,
+    )
+    .may_load(&allocation_key(user, grant))
+}
+
+pub fn get_allocations<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    stage: u128,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<Allocation>, u64)> {
+    let index = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_ALLOCATION_INDEX, &stage.to_be_bytes()],
+        storage,
+    );
+
+    // Rounds without allocations have no index yet
+    let index = AppendStore::<(CanonicalAddr, u64), _, _>::attach(&index);
+    let index = if let Some(result) = index {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let (skip, take) = page_bounds(page, page_size)?;
+    let allocations: StdResult<Vec<Allocation>> = index
+        .iter()
+        .skip(skip)
+        .take(take)
+        .map(|key| {
+            let (user, grant) = key?;
+            let allocation = read_allocation(storage, stage, &user, grant)?
+                .ok_or_else(|| StdError::generic_err("Allocation index is corrupted"))?;
+            Ok(allocation.into_humanized(api.human_address(&user)?, grant))
+        })
+        .collect();
+    allocations.map(|allocations| (allocations, index.len() as u64))
+}
@@ -0,0 +1,149 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    Api, BlockInfo, CanonicalAddr, HumanAddr, ReadonlyStorage, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+use crate::state::Config;
+use crate::utils::page_bounds;
+
+const PREFIX_CLAIMS: &[u8] = b"claims";
+
+// Note that id is a globally incrementing counter shared by every user and stage,
+// so it can be used to order payouts across different histories.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct ClaimTx {
+    pub id: u64,
+    pub stage: Uint128,
+    /// Grant of the position the claim was paid from
+    pub grant: u64,
+    pub recipient: HumanAddr,
+    /// Amount paid at token generation event, zero for routine claims
+    pub tge_amount: Uint128,
+    /// Amount paid from the linear vesting schedule
+    pub linear_amount: Uint128,
+    /// First epoch index covered by the linear amount
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_epoch: Option<u64>,
+    /// Last epoch index (inclusive) covered by the linear amount
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_epoch: Option<u64>,
+    pub block_time: u64,
+    pub block_height: u64,
+}
+
+// Stored types:
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+struct StoredClaimTx {
+    id: u64,
+    stage: u128,
+    grant: u64,
+    recipient: CanonicalAddr,
+    tge_amount: u128,
+    linear_amount: u128,
+    epochs: Option<(u64, u64)>,
+    block_time: u64,
+    block_height: u64,
+}
+
+impl StoredClaimTx {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<ClaimTx> {
+        Ok(ClaimTx {
+            id: self.id,
+            stage: Uint128::from(self.stage),
+            grant: self.grant,
+            recipient: api.human_address(&self.recipient)?,
+            tge_amount: Uint128::from(self.tge_amount),
+            linear_amount: Uint128::from(self.linear_amount),
+            start_epoch: self.epochs.map(|(start, _)| start),
+            end_epoch: self.epochs.map(|(_, end)| end),
+            block_time: self.block_time,
+            block_height: self.block_height,
+        })
+    }
+}
+
+// Storage functions:
+
+fn increment_claim_count<S: Storage>(store: &mut S) -> StdResult<u64> {
+    let mut config = Config::from_storage(store);
+    let id = config.claim_count() + 1;
+    config.set_claim_count(id)?;
+    Ok(id)
+}
+
+#[allow(clippy::too_many_arguments)] // We just need them
+pub fn store_claim<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    stage: u128,
+    grant: u64,
+    tge_amount: u128,
+    linear_amount: u128,
+    epochs: Option<(u64, u64)>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let id = increment_claim_count(store)?;
+    let claim = StoredClaimTx {
+        id,
+        stage,
+        grant,
+        recipient: recipient.clone(),
+        tge_amount,
+        linear_amount,
+        epochs,
+        block_time: block.time,
+        block_height: block.height,
+    };
+
+    append_claim(store, &claim, owner)
+}
+
+fn append_claim<S: Storage>(
+    store: &mut S,
+    claim: &StoredClaimTx,
+    for_address: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_CLAIMS, for_address.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(claim)
+}
+
+pub fn get_claims<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    for_address: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<ClaimTx>, u64)> {
+    let store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_CLAIMS, for_address.as_slice()], storage);
+
+    // Try to access the storage of claims for the account.
+    // If it doesn't exist yet, return an empty list of claims.
+    let store = AppendStore::<StoredClaimTx, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    // Take `page_size` claims starting from the latest claim, potentially skipping
+    // `page * page_size` claims from the start.
+    let (skip, take) = page_bounds(page, page_size)?;
+    let claim_iter = store.iter().rev().skip(skip).take(take);
+
+    // The `and_then` here flattens the `StdResult<StdResult<ClaimTx>>` to an `StdResult<ClaimTx>`
+    let claims: StdResult<Vec<ClaimTx>> = claim_iter
+        .map(|claim| claim.map(|claim| claim.into_humanized(api)).and_then(|x| x))
+        .collect();
+    claims.map(|claims| (claims, store.len() as u64))
+}
@@ -7,12 +7,18 @@ use serde::{Deserialize, Serialize};
 pub enum ContractStatusLevel {
     NormalRun,
     StopAll,
+    /// New vesting rounds can't be registered, everything else keeps running
+    StopRegistrations,
+    /// Users can't claim, admin operations keep running
+    StopClaims,
 }
 
 pub fn status_level_to_u8(status_level: ContractStatusLevel) -> u8 {
     match status_level {
         ContractStatusLevel::NormalRun => 0,
         ContractStatusLevel::StopAll => 1,
+        ContractStatusLevel::StopRegistrations => 2,
+        ContractStatusLevel::StopClaims => 3,
     }
 }
 
@@ -20,9 +26,25 @@ pub fn u8_to_status_level(status_level: u8) -> StdResult<ContractStatusLevel> {
     match status_level {
         0 => Ok(ContractStatusLevel::NormalRun),
         1 => Ok(ContractStatusLevel::StopAll),
+        2 => Ok(ContractStatusLevel::StopRegistrations),
+        3 => Ok(ContractStatusLevel::StopClaims),
         _ => Err(StdError::generic_err("Invalid state level")),
     }
 }
 
 pub const TGE_PRECISION: u128 = 10000;
-pub const ONE_DAY_IN_SECONDS: u64 = 24 * 60 * 60;
\ No newline at end of file
+pub const ONE_DAY_IN_SECONDS: u64 = 24 * 60 * 60;
+/// Basis points in a whole payout
+pub const BPS_PRECISION: u128 = 10000;
+/// Highest claim fee a round can charge, in basis points
+pub const MAX_CLAIM_FEE_BPS: u16 = 1000;
+/// Allocations accepted by a single `AddAllocations`, to stay within the gas limit
+pub const MAX_ALLOCATIONS_PER_MSG: usize = 100;
+/// Positions accepted by a single `ImportPositions`
+pub const MAX_IMPORTS_PER_MSG: usize = 100;
+/// Most entries a paginated query returns at once, to stay within the query gas limit
+pub const MAX_PAGE_SIZE: u32 = 100;
+/// Longest name of a round, in bytes
+pub const MAX_ROUND_NAME_LEN: usize = 64;
+/// Longest description or uri of a round, in bytes
+pub const MAX_ROUND_TEXT_LEN: usize = 1024;
@@ -1,22 +1,58 @@
 use cosmwasm_std::{
-    debug_print, log, to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse,
-    HandleResult, HumanAddr, InitResponse, InitResult, Querier, StdError, StdResult, Storage,
-    Uint128,
+    debug_print, from_binary, log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg,
+    Env, Extern, HandleResponse, HandleResult, HumanAddr, InitResponse, InitResult, LogAttribute,
+    Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
 };
 use secret_toolkit::snip20;
+use secret_toolkit::utils::{HandleCallback, Query};
 
 use crate::{
+    allocations::{add_allocation, get_allocations, read_allocation, Allocation, StoredAllocation},
+    claim_history::{get_claims, store_claim},
     constants::{
-        status_level_to_u8, u8_to_status_level, ContractStatusLevel, ONE_DAY_IN_SECONDS,
-        TGE_PRECISION,
+        status_level_to_u8, u8_to_status_level, ContractStatusLevel, MAX_ALLOCATIONS_PER_MSG,
+        MAX_CLAIM_FEE_BPS, MAX_IMPORTS_PER_MSG, MAX_PAGE_SIZE, MAX_ROUND_NAME_LEN,
+        MAX_ROUND_TEXT_LEN, ONE_DAY_IN_SECONDS, TGE_PRECISION,
+    },
+    error::ContractError,
+    merkle_proof::{
+        multiproof::{verify_multiproof, MultiProof},
+        vesting_stats_verify::{user_vesting_leaf, verify_user_vesting_stats},
+    },
+    msg::{
+        space_pad, AllocationsResponse, ClaimHistoryResponse, ContractStatusResponse,
+        ContractViewingKeyResponse, EmergencyWithdrawalsResponse, EscrowBalanceResponse,
+        ExportPositionsResponse, HandleAnswer, HandleMsg, InitMsg, QueryMsg, ReceiveMsg,
+        ReconcileResponse, ResponseStatus, RoundFee, RoundFundingStatusResponse, RoundStaking,
+        RoundStatsResponse, StakingStatusResponse, UnlockScheduleResponse, VestingRoundResponse,
+        VestingRoundsResponse,
+    },
+    positions::{get_indexed_positions, index_position, PositionImport, PositionRecord},
+    reconcile::{check_position, Discrepancy, DiscrepancyKind},
+    signature::vesting_stats_verify::{
+        allocation_message_hash, validate_public_key, verify_allocation_signature,
+    },
+    staking::{
+        accrue_rewards, position_staked, read_staking_state, write_staking_state, StakingContract,
+        StakingHandleMsg, StakingQueryAnswer, StakingQueryMsg, StakingReceiveMsg, StakingState,
     },
-    merkle_proof::vesting_stats_verify::verify_user_vesting_stats,
-    msg::{HandleMsg, InitMsg, QueryMsg, VestingRoundResponse},
     state::{
-        read_user_vesting_stats, write_user_vesting_stats, Config, ReadonlyConfig,
-        ReadonlyVestingRound, UserVestingStatsState, VestingRound, VestingRoundState,
+        read_round_metadata, read_round_stats, read_user_vesting_stats, read_viewing_key,
+        write_round_metadata, write_round_stats, write_user_vesting_stats, write_viewing_key,
+        AllocationMode, Allocator, ClaimFee, Config, EpochAnchor, ReadonlyConfig,
+        ReadonlyVestingRound, RoundMetadata, UserVestingStatsState, VestingAsset, VestingRound,
+        VestingRoundState,
     },
-    vesting::calc_vesting_schedule::calc_current_vesting_amount,
+    utils::{page_bounds, Duration},
+    vesting::{
+        calc_vesting_schedule::{
+            calc_current_vesting_amount, calc_tge_amount, epoch_index, epoch_length,
+            first_claim_epoch_index, schedule_now,
+        },
+        unlock_schedule::{unlock_events, UnlockEvent},
+    },
+    viewing_key::{ViewingKey, VIEWING_KEY_SIZE},
+    withdrawals::{get_withdrawals, store_withdrawal},
 };
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -24,23 +60,40 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: InitMsg,
 ) -> InitResult {
-    let owner = msg.owner.map_or(
-        Ok(deps.api.canonical_address(&env.message.sender)?),
-        |addr| Ok(deps.api.canonical_address(&addr)?),
-    )?;
+    let owner = deps
+        .api
+        .canonical_address(&msg.owner.unwrap_or_else(|| env.message.sender.clone()))?;
 
     let contract_status = u8_to_status_level(msg.contract_status.map_or(0, |status| status))?;
 
+    // Viewing key set on every round token, so balances are only readable by the owner
+    let contract_viewing_key = ViewingKey::new(&env, &msg.prng_seed.0, b"vesting");
+
     let mut config_storage = Config::from_storage(&mut deps.storage);
 
     config_storage.set_contract_owner(&owner)?;
     config_storage.set_contract_status(contract_status)?;
+    config_storage.set_contract_address(&deps.api.canonical_address(&env.contract.address)?)?;
+    config_storage.set_private_amounts(msg.private_amounts.unwrap_or(false))?;
+    config_storage.set_contract_viewing_key(&contract_viewing_key)?;
 
     debug_print!("Contract was initialized by {}", env.message.sender);
 
     Ok(InitResponse::default())
 }
 
+pub const RESPONSE_BLOCK_SIZE: usize = 256;
+
+fn pad_response(response: StdResult<HandleResponse>) -> StdResult<HandleResponse> {
+    response.map(|mut response| {
+        response.data = response.data.map(|mut data| {
+            space_pad(RESPONSE_BLOCK_SIZE, &mut data.0);
+            data
+        });
+        response
+    })
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -51,137 +104,419 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     match contract_status {
         ContractStatusLevel::StopAll => {
             let response = match msg {
-                HandleMsg::SetContractStatus { level } => try_set_contract_status(deps, env, level),
+                HandleMsg::SetContractStatus { level, .. } => {
+                    try_set_contract_status(deps, env, level)
+                }
+                HandleMsg::EmergencyWithdraw {
+                    stage,
+                    token,
+                    amount,
+                    recipient,
+                    ..
+                } => {
+                    try_emergency_withdraw(deps, env, stage.u128(), token, amount.u128(), recipient)
+                }
                 _ => Err(StdError::generic_err(
                     "This contract is stopped and this action is not allowed",
                 )),
             };
-            return response;
+            return pad_response(response);
+        }
+        ContractStatusLevel::StopRegistrations => {
+            if let HandleMsg::RegisterNewVestingRound { .. } = msg {
+                return pad_response(Err(StdError::generic_err(
+                    "Registration of new vesting rounds is stopped",
+                )));
+            }
+        }
+        ContractStatusLevel::StopClaims => {
+            if let HandleMsg::Claim { .. } = msg {
+                return pad_response(Err(StdError::generic_err("Claims are stopped")));
+            }
         }
         ContractStatusLevel::NormalRun => {} // If it's a normal run just continue
     }
 
-    match msg {
+    let response = match msg {
         HandleMsg::RegisterNewVestingRound {
             merkle_root,
             token_address,
             is_paused,
             token_code_hash,
+            denom,
+            custody,
+            total_allocation,
+            epoch,
+            epoch_anchor,
+            import_positions,
             distribution,
+            allocator,
+            explicit,
+            metadata,
+            fee,
+            staking,
+            ..
         } => try_register_new_round(
             deps,
             env,
             is_paused,
+            import_positions,
             distribution,
             token_address,
             token_code_hash,
+            denom,
+            custody,
+            total_allocation,
+            epoch,
+            epoch_anchor,
             merkle_root,
+            allocator,
+            explicit,
+            metadata,
+            fee,
+            staking,
         ),
-        HandleMsg::SetContractStatus { level } => try_set_contract_status(deps, env, level),
-        HandleMsg::GrantContractOwner { new_admin } => {
+        HandleMsg::FundRound { stage, .. } => try_fund_round(deps, env, stage.u128()),
+        HandleMsg::ActivateRound { stage, .. } => try_activate_round(deps, env, stage.u128()),
+        HandleMsg::SetRoundPaused {
+            stage, is_paused, ..
+        } => try_set_round_paused(deps, env, stage.u128(), is_paused),
+        HandleMsg::ImportPositions {
+            stage,
+            positions,
+            multiproof,
+            ..
+        } => try_import_positions(deps, env, stage.u128(), positions, multiproof),
+        HandleMsg::Receive {
+            from, amount, msg, ..
+        } => try_receive(deps, env, from, amount.u128(), msg),
+        HandleMsg::SetContractStatus { level, .. } => try_set_contract_status(deps, env, level),
+        HandleMsg::SetPrivateAmounts { enabled, .. } => try_set_private_amounts(deps, env, enabled),
+        HandleMsg::SetRoundMetadata {
+            stage, metadata, ..
+        } => try_set_round_metadata(deps, env, stage.u128(), metadata),
+        HandleMsg::GrantContractOwner { new_admin, .. } => {
             try_transfer_contract_owner(deps, env, new_admin)
         }
-        HandleMsg::ClaimContractOwner {} => try_claim_contract_owner(deps, env),
-        HandleMsg::RevokeGrantedContractOwner {} => try_revoke_granted_contract_owner(deps, env),
+        HandleMsg::ClaimContractOwner { .. } => try_claim_contract_owner(deps, env),
+        HandleMsg::RevokeGrantedContractOwner { .. } => {
+            try_revoke_granted_contract_owner(deps, env)
+        }
         HandleMsg::Claim {
             proof,
+            signature,
             stage,
+            grant,
             amount,
             tge,
             start_at,
             duration,
             cliff,
+            msg,
+            ..
         } => try_claim(
             deps,
             env,
             proof,
+            signature,
             stage.u128(),
+            grant.unwrap_or(0),
             amount.u128(),
             tge.u128(),
             start_at,
             cliff,
             duration,
+            msg,
         ),
-    }
+        HandleMsg::SetClaimMsg {
+            stage, grant, msg, ..
+        } => try_set_claim_msg(deps, env, stage.u128(), grant.unwrap_or(0), msg),
+        HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, key),
+        HandleMsg::AddAllocations {
+            stage, allocations, ..
+        } => try_add_allocations(deps, env, stage.u128(), allocations),
+        HandleMsg::SweepStakingRewards {
+            stage, recipient, ..
+        } => try_sweep_staking_rewards(deps, env, stage.u128(), recipient),
+        HandleMsg::EmergencyWithdraw { .. } => Err(StdError::generic_err(
+            "Emergency withdrawals are only allowed while the contract is stopped",
+        )),
+    };
+
+    pad_response(response)
 }
 
 // ================= Execution handler ===================
 
+/// Checks that `allocation` is a grant of the round, with the proof its allocation mode needs
+fn verify_grant<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_address: &HumanAddr,
+    allocation_mode: &AllocationMode,
+    stage: u128,
+    allocation: &Allocation,
+    proof: Option<Vec<String>>,
+    signature: Option<Binary>,
+) -> StdResult<()> {
+    match (allocation_mode, signature) {
+        (AllocationMode::Merkle { merkle_root }, None) => {
+            verify_user_vesting_stats(
+                merkle_root,
+                proof.unwrap_or_default(),
+                allocation.address.clone(),
+                stage,
+                allocation.grant,
+                allocation.amount.u128(),
+                allocation.tge.u128(),
+                allocation.start_at,
+                allocation.cliff,
+                allocation.duration,
+            )?;
+        }
+        (AllocationMode::Signature { allocator }, Some(signature)) => {
+            let message_hash = allocation_message_hash(
+                contract_address,
+                &allocation.address,
+                stage,
+                allocation.grant,
+                allocation.amount.u128(),
+                allocation.tge.u128(),
+                allocation.start_at,
+                allocation.cliff,
+                allocation.duration,
+            );
+            verify_allocation_signature(
+                &deps.api,
+                &allocator.algorithm,
+                &allocator.public_key,
+                &message_hash,
+                &signature,
+            )?;
+        }
+        (AllocationMode::Explicit { .. }, None) => {
+            let stored = read_allocation(
+                &deps.storage,
+                stage,
+                &deps.api.canonical_address(&allocation.address)?,
+                allocation.grant,
+            )?
+            .ok_or_else(|| StdError::generic_err("No allocation for this address!"))?;
+
+            if stored
+                != (StoredAllocation {
+                    amount: allocation.amount,
+                    tge: allocation.tge,
+                    start_at: allocation.start_at,
+                    cliff: allocation.cliff,
+                    duration: allocation.duration,
+                })
+            {
+                return Err(StdError::generic_err(
+                    "Claim does not match the stored allocation!",
+                ));
+            }
+        }
+        (AllocationMode::Explicit { .. }, Some(_)) => {
+            return Err(StdError::generic_err(
+                "This round is claimed with stored allocations, not signatures!",
+            ));
+        }
+        (AllocationMode::Merkle { .. }, Some(_)) => {
+            return Err(StdError::generic_err(
+                "This round is claimed with merkle proofs, not signatures!",
+            ));
+        }
+        (AllocationMode::Signature { .. }, None) => {
+            return Err(StdError::generic_err(
+                "This round is claimed with allocator signatures!",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn try_claim<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    proof: Vec<String>,
+    proof: Option<Vec<String>>,
+    signature: Option<Binary>,
     stage: u128,
+    grant: u64,
     total_amount: u128,
     tge: u128,
     start_at: u64,
     cliff: u64,
     duration: u64,
+    msg: Option<Binary>,
 ) -> StdResult<HandleResponse> {
     let mut output_msgs = vec![];
     let mut logs = vec![];
+    let mut tge_paid = 0u128;
+    let mut linear_paid = 0u128;
+    let mut fee_paid = 0u128;
+    let mut claimed_epochs = None;
+
+    let mut config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    if !config.activated {
+        return Err(StdError::generic_err("Vesting round is not activated yet!"));
+    }
+    if config.is_paused {
+        return Err(StdError::generic_err("Vesting round is paused!"));
+    }
+    let leaf_hash = Binary(
+        user_vesting_leaf(
+            &env.message.sender,
+            stage,
+            grant,
+            total_amount,
+            tge,
+            start_at,
+            cliff,
+            duration,
+        )
+        .to_vec(),
+    );
 
-    verify_user_vesting_stats(
+    verify_grant(
         deps,
-        proof,
-        env.message.sender.clone(),
+        &env.contract.address,
+        &config.allocation,
         stage,
-        total_amount,
-        tge,
-        start_at,
-        cliff,
-        duration,
+        &Allocation {
+            address: env.message.sender.clone(),
+            grant,
+            amount: Uint128::from(total_amount),
+            tge: Uint128::from(tge),
+            start_at,
+            cliff,
+            duration,
+        },
+        proof,
+        signature,
     )?;
 
     let user_vesting_stats = read_user_vesting_stats(
         &deps.storage,
         &deps.api.canonical_address(&env.message.sender)?,
         stage,
+        grant,
     )?;
-    let mut config_storage = VestingRound::from_storage(&mut deps.storage);
-    let mut config = config_storage.config_by_stage(stage)?;
+    let now = schedule_now(&config.epoch, &env.block);
+    let epoch_length = epoch_length(&config.epoch);
 
     // Check if vesting time already started or still in pending state
-    if start_at.gt(&env.block.time) {
+    if start_at.gt(&now) {
         return Err(StdError::generic_err("Claim time have not started yet!"));
     }
 
+    // A message given with the claim replaces the default of the position
+    let claim_msg = msg.or_else(|| {
+        user_vesting_stats
+            .as_ref()
+            .and_then(|stats| stats.claim_msg.clone())
+    });
+
+    // Rewards are added up on the unvested amount before this claim pays any of it
+    let mut staking_msgs = vec![];
+    let mut staking_state = None;
+    if let Some(staking) = &config.staking {
+        let mut state = read_staking_state(&deps.storage, stage)?;
+        staking_msgs.extend(harvest_rewards(deps, &env, staking, &mut state)?);
+        staking_state = Some(state);
+    }
+
     // Create data for user and pay TGE for first time vesting
+    let is_new_position = user_vesting_stats.is_none();
+    let staked_before = user_vesting_stats.as_ref().map_or(0, position_staked);
+    // Positions opened before leaves were recorded predate the position index as well
+    let is_indexed = matches!(&user_vesting_stats, Some(stats) if stats.leaf_hash.is_some());
     let mut user_vesting_stats = match user_vesting_stats {
-        Some(user_vesting_stats) => user_vesting_stats,
+        Some(mut user_vesting_stats) => {
+            // Positions opened before leaves were recorded are bound to the leaf of their fields
+            let position_leaf = match user_vesting_stats.leaf_hash.clone() {
+                Some(position_leaf) => position_leaf,
+                None => {
+                    // They predate `tge_amount` too, their TGE was paid on the claimed amount
+                    if user_vesting_stats.tge_amount.is_zero() {
+                        user_vesting_stats.tge_amount = Uint128::from(calc_tge_amount(
+                            total_amount,
+                            user_vesting_stats.tge.u128(),
+                        )?);
+                    }
+                    Binary(
+                        user_vesting_leaf(
+                            &env.message.sender,
+                            stage,
+                            grant,
+                            user_vesting_stats.total_amount.u128()
+                                + user_vesting_stats.tge_amount.u128(),
+                            user_vesting_stats.tge.u128(),
+                            user_vesting_stats.start_vesting_epoch,
+                            user_vesting_stats.cliff,
+                            user_vesting_stats.vesting_duration,
+                        )
+                        .to_vec(),
+                    )
+                }
+            };
+            if position_leaf != leaf_hash {
+                return Err(StdError::generic_err(
+                    ContractError::LeafMismatch {
+                        stage: Uint128::from(stage),
+                        grant,
+                    }
+                    .to_string(),
+                ));
+            }
+
+            user_vesting_stats.leaf_hash = Some(position_leaf);
+            if let Some(state) = &staking_state {
+                accrue_rewards(&mut user_vesting_stats, state.reward_per_token.u128())?;
+            }
+            user_vesting_stats
+        }
         _ => {
             let mut user_vesting_stats = UserVestingStatsState {
                 tge: Uint128::from(tge),
+                tge_amount: Uint128::zero(),
                 cliff,
-                next_claim_epoch_index: start_at
-                    .checked_add(cliff)
-                    .unwrap()
-                    .checked_div(ONE_DAY_IN_SECONDS)
-                    .unwrap(),
+                next_claim_epoch_index: first_claim_epoch_index(
+                    start_at,
+                    cliff,
+                    epoch_length,
+                    &config.epoch_anchor,
+                ),
                 total_amount: Uint128::from(total_amount),
                 total_claimed: Uint128::from(0u128),
                 user: deps.api.canonical_address(&env.message.sender)?,
+                grant,
                 start_vesting_epoch: start_at,
+                leaf_hash: Some(leaf_hash.clone()),
+                claim_msg: None,
+                // Positions earn the rewards harvested after they open
+                reward_per_token_paid: staking_state
+                    .as_ref()
+                    .map_or_else(Uint128::zero, |state| state.reward_per_token),
+                rewards: Uint128::zero(),
                 vesting_duration: duration,
             };
 
             if tge.gt(&0u128) {
-                let tge_amount = total_amount
-                    .checked_mul(tge)
-                    .unwrap()
-                    .checked_div(TGE_PRECISION)
-                    .unwrap();
+                let tge_amount = calc_tge_amount(total_amount, tge)?;
 
-                output_msgs.push(snip20::transfer_from_msg(
-                    deps.api.human_address(&config.distribution)?,
+                let (msgs, fee) = claim_payout_msgs(
+                    deps,
+                    &env,
+                    &config,
                     env.message.sender.clone(),
-                    Uint128::from(tge_amount),
-                    Some(String::from("Pay TGE")),
-                    None,
-                    256,
-                    config.token_code_hash.clone(),
-                    deps.api.human_address(&config.token_address.clone())?,
-                )?);
+                    tge_amount,
+                    claim_msg.clone(),
+                    "Pay TGE",
+                )?;
+                output_msgs.extend(msgs);
+                fee_paid += fee;
 
                 user_vesting_stats.total_claimed = Uint128::from(
                     user_vesting_stats
@@ -201,7 +536,8 @@ fn try_claim<S: Storage, A: Api, Q: Querier>(
                 config.total_claimed =
                     Uint128::from(config.total_claimed.u128().checked_add(tge_amount).unwrap());
 
-                logs.push(log("tge_amount", tge_amount));
+                user_vesting_stats.tge_amount = Uint128::from(tge_amount);
+                tge_paid = tge_amount;
             }
 
             user_vesting_stats
@@ -209,20 +545,28 @@ fn try_claim<S: Storage, A: Api, Q: Querier>(
     };
 
     // Check if current time passed over cliff period
-    let is_cliff_passed = env.block.time.gt(&user_vesting_stats
+    let is_cliff_passed = now.gt(&user_vesting_stats
         .start_vesting_epoch
         .checked_add(user_vesting_stats.cliff)
         .unwrap());
 
     // check whether there exists remaining tokens amount to claim
-    if user_vesting_stats.total_claimed >= user_vesting_stats.total_amount {
+    if tge_paid == 0
+        && user_vesting_stats.linear_claimed() >= user_vesting_stats.total_amount.u128()
+    {
         return Err(StdError::generic_err("Exceeds maximum claim amount!"));
     }
 
     // Check if cliff period is already passed or not
     match is_cliff_passed {
         true => {
-            let current_epoch_index = env.block.time.checked_div(ONE_DAY_IN_SECONDS).unwrap();
+            let current_epoch_index = epoch_index(
+                now,
+                user_vesting_stats.start_vesting_epoch,
+                user_vesting_stats.cliff,
+                epoch_length,
+                &config.epoch_anchor,
+            );
 
             // Check if routine claim is already vested
             if current_epoch_index < user_vesting_stats.next_claim_epoch_index {
@@ -232,23 +576,36 @@ fn try_claim<S: Storage, A: Api, Q: Querier>(
             // Calculate claim amount by daily
             let (claim_amount, next_claim_epoch_index) = calc_current_vesting_amount(
                 current_epoch_index,
-                user_vesting_stats.next_claim_epoch_index,
+                first_claim_epoch_index(
+                    user_vesting_stats.start_vesting_epoch,
+                    user_vesting_stats.cliff,
+                    epoch_length,
+                    &config.epoch_anchor,
+                ),
                 user_vesting_stats.vesting_duration,
                 user_vesting_stats.total_amount.u128(),
+                user_vesting_stats.linear_claimed(),
+                epoch_length,
             )?;
 
             if claim_amount.gt(&0u128) {
-                output_msgs.push(snip20::transfer_from_msg(
-                    deps.api.human_address(&config.distribution)?,
+                let (msgs, fee) = claim_payout_msgs(
+                    deps,
+                    &env,
+                    &config,
                     env.message.sender.clone(),
-                    Uint128::from(claim_amount),
-                    Some(String::from("Pay Claim amount")),
-                    None,
-                    256,
-                    config.token_code_hash.clone(),
-                    deps.api.human_address(&config.token_address.clone())?,
-                )?);
+                    claim_amount,
+                    claim_msg.clone(),
+                    "Pay Claim amount",
+                )?;
+                output_msgs.extend(msgs);
+                fee_paid += fee;
 
+                claimed_epochs = Some((
+                    user_vesting_stats.next_claim_epoch_index,
+                    current_epoch_index,
+                ));
+                linear_paid = claim_amount;
                 user_vesting_stats.next_claim_epoch_index = next_claim_epoch_index;
                 user_vesting_stats.total_claimed = Uint128::from(
                     user_vesting_stats
@@ -266,7 +623,6 @@ fn try_claim<S: Storage, A: Api, Q: Querier>(
                         .unwrap(),
                 );
 
-                logs.push(log("claim_amount", claim_amount));
                 logs.push(log("claim_at", env.block.time));
             }
         }
@@ -281,18 +637,237 @@ fn try_claim<S: Storage, A: Api, Q: Querier>(
         }
     };
 
+    // Payouts bring back their principal from the staking contract, and every claim pays
+    // the rewards the position earned
+    let mut reward_paid = 0u128;
+    if let (Some(staking), Some(state)) = (&config.staking, &mut staking_state) {
+        let principal = tge_paid + linear_paid;
+        if principal > 0 {
+            state.total_staked = Uint128::from(
+                state
+                    .total_staked
+                    .u128()
+                    .checked_sub(principal)
+                    .ok_or_else(|| {
+                        StdError::generic_err(
+                            ContractError::InsufficientFunds {
+                                balance: state.total_staked,
+                                amount: Uint128::from(principal),
+                            }
+                            .to_string(),
+                        )
+                    })?,
+            );
+            staking_msgs.push(
+                StakingHandleMsg::Unstake {
+                    amount: Uint128::from(principal),
+                }
+                .to_cosmos_msg(
+                    staking.code_hash.clone(),
+                    deps.api.human_address(&staking.address)?,
+                    None,
+                )?,
+            );
+        }
+
+        reward_paid = user_vesting_stats.rewards.u128();
+        if reward_paid > 0 {
+            output_msgs.push(reward_payout_msg(
+                deps,
+                &config,
+                env.message.sender.clone(),
+                reward_paid,
+                claim_msg.clone(),
+            )?);
+            state.rewards_paid = Uint128::from(state.rewards_paid.u128() + reward_paid);
+            user_vesting_stats.rewards = Uint128::zero();
+        }
+        state.move_position_stake(staked_before, position_staked(&user_vesting_stats));
+    }
+
+    if tge_paid > 0 || linear_paid > 0 {
+        check_solvency(deps, &env, stage, &config, tge_paid + linear_paid)?;
+    }
+
+    let mut config_storage = VestingRound::from_storage(&mut deps.storage);
     config_storage.make_config(stage, &config)?;
     write_user_vesting_stats(&mut deps.storage, &user_vesting_stats, stage)?;
+    if let Some(state) = &staking_state {
+        write_staking_state(&mut deps.storage, stage, state)?;
+    }
+    if !is_indexed {
+        index_position(&mut deps.storage, stage, &user_vesting_stats.user, grant)?;
+    }
+
+    let mut round_stats = read_round_stats(&deps.storage, stage)?;
+    if is_new_position {
+        round_stats.positions += 1;
+    }
+    if tge_paid > 0 || linear_paid > 0 {
+        round_stats.record_claim(tge_paid, linear_paid, env.block.time);
+        round_stats.fees_paid = Uint128::from(round_stats.fees_paid.u128() + fee_paid);
+    }
+    // Claims on fully vested positions are turned down, so this is the claim that completed it
+    if (is_new_position || linear_paid > 0)
+        && user_vesting_stats.linear_claimed() >= user_vesting_stats.total_amount.u128()
+    {
+        round_stats.fully_vested_positions += 1;
+    }
+    write_round_stats(&mut deps.storage, stage, &round_stats)?;
+
+    if tge_paid > 0 || linear_paid > 0 {
+        let recipient = deps.api.canonical_address(&env.message.sender)?;
+        store_claim(
+            &mut deps.storage,
+            &user_vesting_stats.user,
+            &recipient,
+            stage,
+            grant,
+            tge_paid,
+            linear_paid,
+            claimed_epochs,
+            &env.block,
+        )?;
+    }
+
+    // Amounts are always returned in the encrypted data
+    let mut amounts = vec![];
+    if tge_paid > 0 {
+        amounts.push(log("tge_amount", tge_paid));
+    }
+    if linear_paid > 0 {
+        amounts.push(log("claim_amount", linear_paid));
+    }
+    if fee_paid > 0 {
+        amounts.push(log("fee_amount", fee_paid));
+    }
+    if reward_paid > 0 {
+        amounts.push(log("reward_amount", reward_paid));
+    }
+    logs.extend(amount_logs(&deps.storage, amounts));
 
     let res = HandleResponse {
-        messages: output_msgs,
+        messages: [staking_msgs, output_msgs].concat(),
         log: [vec![log("event", "claim"), log("status", "success")], logs].concat(),
-        data: None,
+        data: Some(to_binary(&HandleAnswer::Claim {
+            status: ResponseStatus::Success,
+            tge_amount: Uint128::from(tge_paid),
+            claim_amount: Uint128::from(linear_paid),
+            fee_amount: config.fee.as_ref().map(|_| Uint128::from(fee_paid)),
+            reward_amount: config.staking.as_ref().map(|_| Uint128::from(reward_paid)),
+        })?),
     };
 
     Ok(res)
 }
 
+fn try_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: u128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let stage = match msg {
+        Some(msg) => match from_binary(&msg)? {
+            ReceiveMsg::FundRound { stage } => stage.u128(),
+        },
+        None => {
+            return Err(StdError::generic_err(
+                "Deposits must specify the vesting round to fund!",
+            ))
+        }
+    };
+
+    let mut vesting_round_storage = VestingRound::from_storage(&mut deps.storage);
+    let mut config = vesting_round_storage.config_by_stage(stage)?;
+
+    // The message sender is the token contract notifying about the deposit
+    let token = deps.api.canonical_address(&env.message.sender)?;
+    let token_code_hash = match &config.asset {
+        VestingAsset::Snip20 {
+            token_address,
+            token_code_hash,
+        } if config.custody && token_address == &token => token_code_hash.clone(),
+        _ => {
+            return Err(StdError::generic_err(
+                "This vesting round does not accept deposits of this token!",
+            ))
+        }
+    };
+
+    config.total_funded = Uint128::from(
+        config
+            .total_funded
+            .u128()
+            .checked_add(amount)
+            .ok_or_else(|| StdError::generic_err("Funded amount overflow!"))?,
+    );
+    vesting_round_storage.make_config(stage, &config)?;
+
+    // Deposits of staking rounds go straight to the staking contract
+    let mut messages = vec![];
+    if let Some(staking) = &config.staking {
+        let mut staking_state = read_staking_state(&deps.storage, stage)?;
+        messages.extend(harvest_rewards(deps, &env, staking, &mut staking_state)?);
+        messages.push(snip20::send_msg(
+            deps.api.human_address(&staking.address)?,
+            Uint128::from(amount),
+            Some(to_binary(&StakingReceiveMsg::Stake {})?),
+            None,
+            None,
+            256,
+            token_code_hash,
+            env.message.sender.clone(),
+        )?);
+
+        staking_state.total_staked = Uint128::from(staking_state.total_staked.u128() + amount);
+        write_staking_state(&mut deps.storage, stage, &staking_state)?;
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: [
+            vec![
+                log("event", "fund_round"),
+                log("status", "success"),
+                log("stage", stage),
+                log("funder", from),
+            ],
+            amount_logs(
+                &deps.storage,
+                vec![
+                    log("funded_amount", amount),
+                    log("total_funded", config.total_funded),
+                ],
+            ),
+        ]
+        .concat(),
+        data: Some(to_binary(&HandleAnswer::Receive {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_set_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> StdResult<HandleResponse> {
+    let vk = ViewingKey(key);
+
+    let message_sender = deps.api.canonical_address(&env.message.sender)?;
+    write_viewing_key(&mut deps.storage, &message_sender, &vk);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("event", "set_viewing_key"), log("status", "success")],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
 fn try_revoke_granted_contract_owner<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -315,7 +890,9 @@ fn try_revoke_granted_contract_owner<S: Storage, A: Api, Q: Querier>(
     Ok(HandleResponse {
         messages: vec![],
         log: vec![log("event", "revoke_granted_contract_owner")],
-        data: None,
+        data: Some(to_binary(&HandleAnswer::RevokeGrantedContractOwner {
+            status: ResponseStatus::Success,
+        })?),
     })
 }
 
@@ -337,7 +914,9 @@ fn try_claim_contract_owner<S: Storage, A: Api, Q: Querier>(
             log("event", "claim_admin_ownership"),
             log("new_admin", env.message.sender),
         ],
-        data: None,
+        data: Some(to_binary(&HandleAnswer::ClaimContractOwner {
+            status: ResponseStatus::Success,
+        })?),
     })
 }
 
@@ -363,7 +942,9 @@ fn try_transfer_contract_owner<S: Storage, A: Api, Q: Querier>(
             log("granted_admin", new_admin),
             log("created_at", env.block.time),
         ],
-        data: None,
+        data: Some(to_binary(&HandleAnswer::GrantContractOwner {
+            status: ResponseStatus::Success,
+        })?),
     })
 }
 
@@ -388,18 +969,59 @@ fn try_set_contract_status<S: Storage, A: Api, Q: Querier>(
             log("status", "success"),
             log("contract_status", status_level_to_u8(status_level)),
         ],
-        data: None,
+        data: Some(to_binary(&HandleAnswer::SetContractStatus {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn try_set_private_amounts<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let mut config_storage = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(
+        &config_storage,
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
+
+    config_storage.set_private_amounts(enabled)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("event", "set_private_amounts"),
+            log("status", "success"),
+            log("private_amounts", enabled),
+        ],
+        data: Some(to_binary(&HandleAnswer::SetPrivateAmounts {
+            status: ResponseStatus::Success,
+        })?),
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 fn try_register_new_round<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     is_paused: Option<bool>,
+    import_positions: Option<bool>,
     distribution: Option<HumanAddr>,
     token_address: Option<HumanAddr>,
     token_code_hash: Option<String>,
-    merkle_root: String,
+    denom: Option<String>,
+    custody: Option<bool>,
+    total_allocation: Option<Uint128>,
+    epoch: Option<Duration>,
+    epoch_anchor: Option<EpochAnchor>,
+    merkle_root: Option<String>,
+    allocator: Option<Allocator>,
+    explicit: Option<bool>,
+    metadata: Option<RoundMetadata>,
+    fee: Option<RoundFee>,
+    staking: Option<RoundStaking>,
 ) -> StdResult<HandleResponse> {
     let mut output_msgs = vec![];
     let mut config_storage = Config::from_storage(&mut deps.storage);
@@ -410,189 +1032,5597 @@ fn try_register_new_round<S: Storage, A: Api, Q: Querier>(
     )?;
 
     let current_stage = config_storage.new_stage();
+    let contract_viewing_key = config_storage.contract_viewing_key()?;
 
-    let config = validate_round_config(
+    let mut config = validate_round_config(
         deps,
         &env,
         distribution,
         token_address,
         token_code_hash,
+        denom,
+        custody,
+        total_allocation,
+        epoch,
+        epoch_anchor,
         is_paused,
+        import_positions,
         merkle_root,
+        allocator,
+        explicit,
+        fee,
+        staking,
     )?;
 
-    let mut vesting_round_storage = VestingRound::from_storage(&mut deps.storage);
-    vesting_round_storage.make_config(current_stage, &config)?;
+    let mut logs = vec![
+        log("event", "new_vesting_round"),
+        log("status", "success"),
+        log("custody", config.custody),
+        log("epoch", config.epoch),
+        log("epoch_anchor", format!("{:?}", config.epoch_anchor)),
+    ];
+    if let Some(fee) = &config.fee {
+        logs.push(log("fee_bps", fee.rate_bps));
+    }
 
-    let callback_contract_addr = deps.api.human_address(&config.token_address)?;
+    match &config.asset {
+        VestingAsset::Snip20 {
+            token_address,
+            token_code_hash,
+        } => {
+            if !env.message.sent_funds.is_empty() {
+                return Err(StdError::generic_err(
+                    "Native coins cannot be sent to a SNIP-20 vesting round!",
+                ));
+            }
 
-    output_msgs.push(snip20::register_receive_msg(
-        env.contract_code_hash.clone(),
-        None,
-        256,
-        config.token_code_hash.clone(),
-        callback_contract_addr.clone(),
-    )?);
+            let callback_contract_addr = deps.api.human_address(token_address)?;
 
-    output_msgs.push(snip20::set_viewing_key_msg(
-        "Snip20-vesting".into(),
-        None,
-        256,
-        config.token_code_hash.clone(),
-        callback_contract_addr.clone(),
-    )?);
+            output_msgs.push(snip20::register_receive_msg(
+                env.contract_code_hash.clone(),
+                None,
+                256,
+                token_code_hash.clone(),
+                callback_contract_addr.clone(),
+            )?);
 
-    let res = HandleResponse {
-        messages: output_msgs,
-        log: vec![
-            log("event", "new_vesting_round"),
-            log("status", "success"),
-            log("token_code_hash", config.token_code_hash),
-            log("token_address", callback_contract_addr),
-            log("merkle_tree", config.merkle_root),
-            log("created_at", config.created_at),
-            log("stage", current_stage),
-        ],
-        data: None,
-    };
+            output_msgs.push(snip20::set_viewing_key_msg(
+                contract_viewing_key.0.clone(),
+                None,
+                256,
+                token_code_hash.clone(),
+                callback_contract_addr.clone(),
+            )?);
 
-    Ok(res)
-}
+            // Same key on the staking contract, to read the rewards of the escrow
+            if let Some(staking) = &config.staking {
+                let staking_address = deps.api.human_address(&staking.address)?;
+                output_msgs.push(
+                    StakingHandleMsg::SetViewingKey {
+                        key: contract_viewing_key.0,
+                    }
+                    .to_cosmos_msg(
+                        staking.code_hash.clone(),
+                        staking_address.clone(),
+                        None,
+                    )?,
+                );
+                logs.push(log("staking", staking_address));
+            }
 
-// ================= Utility function ===================
+            logs.push(log("token_code_hash", token_code_hash));
+            logs.push(log("token_address", callback_contract_addr));
+        }
+        VestingAsset::Native { denom } => {
+            config.total_funded = Uint128::from(sent_funds_of(&env, denom)?);
 
-fn is_admin<S: Storage>(config: &Config<S>, account: &CanonicalAddr) -> StdResult<bool> {
-    let owner = config.contract_owner()?;
-    if &owner != account {
-        return Ok(false);
+            logs.push(log("denom", denom));
+            logs.extend(amount_logs(
+                &deps.storage,
+                vec![log("funded_amount", config.total_funded)],
+            ));
+        }
     }
 
-    Ok(true)
-}
+    let mut vesting_round_storage = VestingRound::from_storage(&mut deps.storage);
+    vesting_round_storage.make_config(current_stage, &config)?;
 
-fn is_granted_admin<S: Storage>(config: &Config<S>, account: &CanonicalAddr) -> StdResult<bool> {
-    let owner = config.granted_contract_owner()?;
-    if &owner != account {
-        return Ok(false);
+    if let Some(metadata) = metadata {
+        validate_round_metadata(&metadata)?;
+        write_round_metadata(&mut deps.storage, current_stage, &metadata)?;
+        if let Some(name) = metadata.name {
+            logs.push(log("name", name));
+        }
     }
 
-    Ok(true)
+    match &config.allocation {
+        AllocationMode::Merkle { merkle_root } => logs.push(log("merkle_tree", merkle_root)),
+        AllocationMode::Signature { allocator } => {
+            logs.push(log("allocator", allocator.public_key.to_base64()))
+        }
+        AllocationMode::Explicit { .. } => logs.push(log("explicit", true)),
+    }
+    logs.push(log("created_at", config.created_at));
+    logs.push(log("stage", current_stage));
+
+    let res = HandleResponse {
+        messages: output_msgs,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::RegisterNewVestingRound {
+            status: ResponseStatus::Success,
+            stage: Uint128::from(current_stage),
+        })?),
+    };
+
+    Ok(res)
 }
 
-fn check_if_admin<S: Storage>(config: &Config<S>, account: &CanonicalAddr) -> StdResult<()> {
-    if !is_admin(config, account)? {
-        return Err(StdError::generic_err(
-            "This is an admin command. Admin commands can only be run from admin address",
-        ));
-    }
+fn try_set_round_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+    metadata: RoundMetadata,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
 
-    Ok(())
+    // Fails for stages that were never registered
+    ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    validate_round_metadata(&metadata)?;
+    write_round_metadata(&mut deps.storage, stage, &metadata)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("event", "set_round_metadata"),
+            log("status", "success"),
+            log("stage", stage),
+        ],
+        data: Some(to_binary(&HandleAnswer::SetRoundMetadata {
+            status: ResponseStatus::Success,
+        })?),
+    })
 }
 
-fn check_if_granted_admin<S: Storage>(
-    config: &Config<S>,
-    account: &CanonicalAddr,
-) -> StdResult<()> {
-    if !is_granted_admin(config, account)? {
+fn try_set_claim_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+    grant: u64,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    if let (VestingAsset::Native { .. }, Some(_)) = (&config.asset, &msg) {
         return Err(StdError::generic_err(
-            "This is a granted admin command. Granted admin commands can only be run from granted admin address",
+            "Claim messages can only be sent with SNIP-20 tokens!",
         ));
     }
 
-    Ok(())
+    let user = deps.api.canonical_address(&env.message.sender)?;
+    let mut user_vesting_stats = read_user_vesting_stats(&deps.storage, &user, stage, grant)?
+        .ok_or_else(|| StdError::generic_err("No vesting position for this address"))?;
+    user_vesting_stats.claim_msg = msg;
+    write_user_vesting_stats(&mut deps.storage, &user_vesting_stats, stage)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("event", "set_claim_msg"),
+            log("status", "success"),
+            log("stage", stage),
+            log("grant", grant),
+        ],
+        data: Some(to_binary(&HandleAnswer::SetClaimMsg {
+            status: ResponseStatus::Success,
+        })?),
+    })
 }
 
-fn validate_round_config<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    env: &Env,
-    distribution: Option<HumanAddr>,
-    token_address: Option<HumanAddr>,
-    token_code_hash: Option<String>,
-    is_paused: Option<bool>,
-    merkle_root: String,
-) -> StdResult<VestingRoundState> {
-    let is_paused = is_paused.map_or(false, |paused| paused);
+fn try_fund_round<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+) -> StdResult<HandleResponse> {
+    let mut vesting_round_storage = VestingRound::from_storage(&mut deps.storage);
+    let mut config = vesting_round_storage.config_by_stage(stage)?;
 
-    // check merkle root length
-    let mut root_buf: [u8; 32] = [0; 32];
-    let is_valid_merkle_root = hex::decode_to_slice(&merkle_root, &mut root_buf);
+    let funded_amount = match &config.asset {
+        VestingAsset::Native { denom } => sent_funds_of(&env, denom)?,
+        VestingAsset::Snip20 { .. } => {
+            return Err(StdError::generic_err(
+                "Only native vesting rounds can be funded with coins!",
+            ))
+        }
+    };
 
-    if !is_valid_merkle_root.is_ok() {
-        return Err(StdError::generic_err("Invalid merkle tree validation!"));
+    if funded_amount == 0 {
+        return Err(StdError::generic_err("No funds were sent!"));
     }
 
-    // Specify distribution address
-    let distribution_addr = deps.api.canonical_address(
-        &distribution.map_or(Ok(env.message.sender.clone()), |addr| Ok(addr))?,
-    )?;
+    config.total_funded = Uint128::from(
+        config
+            .total_funded
+            .u128()
+            .checked_add(funded_amount)
+            .ok_or_else(|| StdError::generic_err("Funded amount overflow!"))?,
+    );
+    vesting_round_storage.make_config(stage, &config)?;
 
-    let config = match (token_address, token_code_hash) {
-        (Some(token_address), Some(token_code_hash)) => Ok(VestingRoundState {
-            distribution: distribution_addr,
-            created_at: env.block.time,
-            merkle_root,
-            is_paused,
-            token_address: deps.api.canonical_address(&token_address)?,
-            token_code_hash,
-            total_claimed: Uint128::zero(),
-        }),
-        (Some(_), None) => Err(StdError::generic_err("Not a valid token code hash!")),
-        (None, Some(_)) => Err(StdError::generic_err("Not a valid token address!")),
-        _ => Err(StdError::generic_err(
-            "Invalid both token address and token code hash!",
-        )),
-    }?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: [
+            vec![
+                log("event", "fund_round"),
+                log("status", "success"),
+                log("stage", stage),
+                log("funder", env.message.sender),
+            ],
+            amount_logs(
+                &deps.storage,
+                vec![
+                    log("funded_amount", funded_amount),
+                    log("total_funded", config.total_funded),
+                ],
+            ),
+        ]
+        .concat(),
+        data: Some(to_binary(&HandleAnswer::FundRound {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
 
-    Ok(config)
+fn try_add_allocations<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+    allocations: Vec<Allocation>,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
+
+    if allocations.len() > MAX_ALLOCATIONS_PER_MSG {
+        return Err(StdError::generic_err(format!(
+            "At most {} allocations can be added at once!",
+            MAX_ALLOCATIONS_PER_MSG
+        )));
+    }
+
+    let mut config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let mut total_allocated = match config.allocation {
+        AllocationMode::Explicit { total_allocated } => total_allocated.u128(),
+        _ => {
+            return Err(StdError::generic_err(
+                "Allocations can only be added to explicit rounds!",
+            ))
+        }
+    };
+
+    for allocation in allocations.iter() {
+        if allocation.amount.is_zero() || allocation.tge.u128() > TGE_PRECISION {
+            return Err(StdError::generic_err(format!(
+                "Invalid allocation for {}",
+                allocation.address
+            )));
+        }
+        total_allocated = total_allocated
+            .checked_add(allocation.amount.u128())
+            .ok_or_else(|| StdError::generic_err("Allocated amount overflow!"))?;
+    }
+
+    if let Some(total_allocation) = config.total_allocation {
+        if total_allocated > total_allocation.u128() {
+            return Err(StdError::generic_err(format!(
+                "Allocations exceed the round total allocation of {}",
+                total_allocation
+            )));
+        }
+    }
+
+    for allocation in allocations.iter() {
+        add_allocation(
+            &mut deps.storage,
+            stage,
+            &deps.api.canonical_address(&allocation.address)?,
+            allocation.grant,
+            &StoredAllocation {
+                amount: allocation.amount,
+                tge: allocation.tge,
+                start_at: allocation.start_at,
+                cliff: allocation.cliff,
+                duration: allocation.duration,
+            },
+        )?;
+    }
+
+    config.allocation = AllocationMode::Explicit {
+        total_allocated: Uint128::from(total_allocated),
+    };
+    VestingRound::from_storage(&mut deps.storage).make_config(stage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: [
+            vec![
+                log("event", "add_allocations"),
+                log("status", "success"),
+                log("stage", stage),
+                log("count", allocations.len()),
+            ],
+            amount_logs(&deps.storage, vec![log("total_allocated", total_allocated)]),
+        ]
+        .concat(),
+        data: Some(to_binary(&HandleAnswer::AddAllocations {
+            status: ResponseStatus::Success,
+        })?),
+    })
 }
 
-// ================= Query ===================
+fn try_activate_round<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetConfig { stage } => to_binary(&get_config_by_stage(deps, stage.0)?),
-        QueryMsg::GetCurrentStage {} => to_binary(&get_current_stage(deps)?),
-        QueryMsg::ContractOwner {} => to_binary(&get_contract_owner(deps)?),
-        QueryMsg::GrantedContractOwner {} => to_binary(&get_granted_contract_owner(deps)?),
+    let mut config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    if config.activated {
+        return Err(StdError::generic_err("Vesting round is already activated!"));
     }
+
+    config.activated = true;
+    VestingRound::from_storage(&mut deps.storage).make_config(stage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("event", "activate_round"),
+            log("status", "success"),
+            log("stage", stage),
+        ],
+        data: Some(to_binary(&HandleAnswer::ActivateRound {
+            status: ResponseStatus::Success,
+        })?),
+    })
 }
 
-fn get_config_by_stage<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
+fn try_set_round_paused<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
     stage: u128,
-) -> StdResult<VestingRoundResponse> {
-    let config_storage = ReadonlyVestingRound::from_storage(&deps.storage);
-    let config = config_storage.config_by_stage(stage)?;
-    Ok(VestingRoundResponse {
-        stage: Uint128::from(stage),
-        total_claimed: config.total_claimed,
-        merkle_root: config.merkle_root,
-        token_address: deps.api.human_address(&config.token_address)?.to_string(),
-        created_at: config.created_at,
+    is_paused: bool,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
+
+    let mut config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    config.is_paused = is_paused;
+    VestingRound::from_storage(&mut deps.storage).make_config(stage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("event", "set_round_paused"),
+            log("status", "success"),
+            log("stage", stage),
+            log("is_paused", is_paused),
+        ],
+        data: Some(to_binary(&HandleAnswer::SetRoundPaused {
+            status: ResponseStatus::Success,
+        })?),
     })
 }
 
-fn get_current_stage<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Uint128> {
-    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
-    let current_stage = Uint128::from(config_storage.current_stage());
-    Ok(current_stage)
+fn try_import_positions<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+    positions: Vec<PositionImport>,
+    multiproof: Option<MultiProof>,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
+
+    if positions.len() > MAX_IMPORTS_PER_MSG {
+        return Err(StdError::generic_err(format!(
+            "At most {} positions can be imported at once!",
+            MAX_IMPORTS_PER_MSG
+        )));
+    }
+
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    if config.activated {
+        return Err(StdError::generic_err(
+            "Positions can only be imported before the round is activated!",
+        ));
+    }
+    let multiproof_root = match (&multiproof, &config.allocation) {
+        (None, _) => None,
+        (Some(_), AllocationMode::Merkle { merkle_root }) => Some(merkle_root),
+        (Some(_), _) => {
+            return Err(StdError::generic_err(
+                "Multiproofs can only be used in merkle rounds!",
+            ))
+        }
+    };
+    let epoch_length = epoch_length(&config.epoch);
+    let mut round_stats = read_round_stats(&deps.storage, stage)?;
+    let mut imported_amount = round_stats.imported_amount.u128();
+    let mut imported_claimed = round_stats.imported_claimed.u128();
+
+    // Every position is checked before anything is written
+    let mut imported: Vec<UserVestingStatsState> = Vec::with_capacity(positions.len());
+    let mut leaves: Vec<[u8; 32]> = Vec::with_capacity(positions.len());
+    for PositionImport {
+        position,
+        proof,
+        signature,
+    } in positions
+    {
+        let user = deps.api.canonical_address(&position.address)?;
+        let invalid_position = || {
+            StdError::generic_err(format!(
+                "Invalid position for grant {} of {}",
+                position.grant, position.address
+            ))
+        };
+
+        if position.amount.is_zero()
+            || position.tge.u128() > TGE_PRECISION
+            || position.tge_amount.u128() > position.amount.u128()
+        {
+            return Err(invalid_position());
+        }
+        let already_imported = imported
+            .iter()
+            .any(|stats| stats.user == user && stats.grant == position.grant);
+        if already_imported
+            || read_user_vesting_stats(&deps.storage, &user, stage, position.grant)?.is_some()
+        {
+            return Err(StdError::generic_err(format!(
+                "A position already exists for grant {} of {}",
+                position.grant, position.address
+            )));
+        }
+
+        if multiproof.is_none() {
+            verify_grant(
+                deps,
+                &env.contract.address,
+                &config.allocation,
+                stage,
+                &Allocation {
+                    address: position.address.clone(),
+                    grant: position.grant,
+                    amount: position.amount,
+                    tge: position.tge,
+                    start_at: position.start_at,
+                    cliff: position.cliff,
+                    duration: position.duration,
+                },
+                proof,
+                signature,
+            )?;
+        } else if proof.is_some() || signature.is_some() {
+            return Err(StdError::generic_err(
+                "Positions proven by a multiproof can't carry their own proof!",
+            ));
+        }
+
+        let leaf = user_vesting_leaf(
+            &position.address,
+            stage,
+            position.grant,
+            position.amount.u128(),
+            position.tge.u128(),
+            position.start_at,
+            position.cliff,
+            position.duration,
+        );
+        leaves.push(leaf);
+        let leaf_hash = Binary(leaf.to_vec());
+        let stats = UserVestingStatsState {
+            user,
+            grant: position.grant,
+            total_amount: Uint128::from(position.amount.u128() - position.tge_amount.u128()),
+            total_claimed: position.total_claimed,
+            vesting_duration: position.duration,
+            cliff: position.cliff,
+            tge: position.tge,
+            tge_amount: position.tge_amount,
+            start_vesting_epoch: position.start_at,
+            leaf_hash: Some(leaf_hash),
+            claim_msg: None,
+            reward_per_token_paid: Uint128::zero(),
+            rewards: Uint128::zero(),
+            next_claim_epoch_index: position.next_claim_epoch_index,
+        };
+
+        // Claimed amounts must be the ones the schedule pays up to `next_claim_epoch_index`
+        if !check_position(
+            &position.address,
+            &stats,
+            epoch_length,
+            &config.epoch_anchor,
+        )?
+        .is_empty()
+        {
+            return Err(invalid_position());
+        }
+
+        imported_amount = imported_amount
+            .checked_add(position.amount.u128())
+            .ok_or_else(|| StdError::generic_err("Imported amount overflow!"))?;
+        imported_claimed += position.total_claimed.u128();
+        imported.push(stats);
+    }
+
+    if let (Some(multiproof), Some(merkle_root)) = (&multiproof, multiproof_root) {
+        verify_multiproof(merkle_root, &leaves, multiproof)?;
+    }
+
+    if let Some(total_allocation) = config.total_allocation {
+        if imported_amount > total_allocation.u128() {
+            return Err(StdError::generic_err(format!(
+                "Imported positions exceed the round total allocation of {}",
+                total_allocation
+            )));
+        }
+    }
+
+    // Imported positions earn the rewards harvested after they are written
+    let mut messages = vec![];
+    if let Some(staking) = &config.staking {
+        let mut staking_state = read_staking_state(&deps.storage, stage)?;
+        messages.extend(harvest_rewards(deps, &env, staking, &mut staking_state)?);
+        for stats in imported.iter_mut() {
+            stats.reward_per_token_paid = staking_state.reward_per_token;
+            staking_state.move_position_stake(0, position_staked(stats));
+        }
+        write_staking_state(&mut deps.storage, stage, &staking_state)?;
+    }
+
+    for stats in imported.iter() {
+        write_user_vesting_stats(&mut deps.storage, stats, stage)?;
+        index_position(&mut deps.storage, stage, &stats.user, stats.grant)?;
+
+        round_stats.positions += 1;
+        if stats.linear_claimed() >= stats.total_amount.u128() {
+            round_stats.fully_vested_positions += 1;
+        }
+    }
+    round_stats.imported_amount = Uint128::from(imported_amount);
+    round_stats.imported_claimed = Uint128::from(imported_claimed);
+    write_round_stats(&mut deps.storage, stage, &round_stats)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("event", "import_positions"),
+            log("status", "success"),
+            log("stage", stage),
+            log("count", imported.len()),
+        ],
+        data: Some(to_binary(&HandleAnswer::ImportPositions {
+            status: ResponseStatus::Success,
+        })?),
+    })
 }
 
-fn get_contract_owner<S: Storage, A: Api, Q: Querier>(
+fn try_sweep_staking_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+    recipient: HumanAddr,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
+
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let staking = config
+        .staking
+        .as_ref()
+        .ok_or_else(|| StdError::generic_err("This vesting round does not stake its escrow"))?;
+
+    let mut staking_state = read_staking_state(&deps.storage, stage)?;
+    let mut messages = vec![];
+    messages.extend(harvest_rewards(deps, &env, staking, &mut staking_state)?);
+
+    let amount = staking_state.unassigned_rewards.u128();
+    if amount == 0 {
+        return Err(StdError::generic_err(
+            "No unassigned staking rewards to sweep",
+        ));
+    }
+    messages.push(reward_payout_msg(
+        deps,
+        &config,
+        recipient.clone(),
+        amount,
+        None,
+    )?);
+
+    staking_state.unassigned_rewards = Uint128::zero();
+    staking_state.rewards_swept = Uint128::from(staking_state.rewards_swept.u128() + amount);
+    write_staking_state(&mut deps.storage, stage, &staking_state)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: [
+            vec![
+                log("event", "sweep_staking_rewards"),
+                log("status", "success"),
+                log("stage", stage),
+                log("recipient", recipient),
+            ],
+            amount_logs(&deps.storage, vec![log("amount", amount)]),
+        ]
+        .concat(),
+        data: Some(to_binary(&HandleAnswer::SweepStakingRewards {
+            status: ResponseStatus::Success,
+            amount: Uint128::from(amount),
+        })?),
+    })
+}
+
+fn try_emergency_withdraw<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stage: u128,
+    token: String,
+    amount: u128,
+    recipient: HumanAddr,
+) -> StdResult<HandleResponse> {
+    check_if_admin(
+        &Config::from_storage(&mut deps.storage),
+        &deps.api.canonical_address(&env.message.sender)?,
+    )?;
+
+    let mut config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    if !config.custody {
+        return Err(StdError::generic_err(
+            "This round is paid from the distribution wallet and holds no funds",
+        ));
+    }
+    if amount == 0 {
+        return Err(StdError::generic_err("Withdrawn amount must be positive"));
+    }
+
+    // The escrow accounting is not trusted here, the contract is stopped because something
+    // went wrong, so the transfer itself is what limits the amount.
+    let mut messages = vec![];
+    let message = match &config.asset {
+        VestingAsset::Snip20 {
+            token_address,
+            token_code_hash,
+        } => {
+            let token_address = deps.api.human_address(token_address)?;
+            if token_address.0 != token {
+                return Err(StdError::generic_err(format!(
+                    "Round {} does not hold token {}",
+                    stage, token
+                )));
+            }
+            if let Some(staking) = &config.staking {
+                let mut staking_state = read_staking_state(&deps.storage, stage)?;
+                staking_state.total_staked =
+                    Uint128::from(staking_state.total_staked.u128().saturating_sub(amount));
+                write_staking_state(&mut deps.storage, stage, &staking_state)?;
+
+                messages.push(
+                    StakingHandleMsg::Unstake {
+                        amount: Uint128::from(amount),
+                    }
+                    .to_cosmos_msg(
+                        staking.code_hash.clone(),
+                        deps.api.human_address(&staking.address)?,
+                        None,
+                    )?,
+                );
+            }
+            snip20::transfer_msg(
+                recipient.clone(),
+                Uint128::from(amount),
+                Some(format!("Emergency withdrawal from vesting round {}", stage)),
+                None,
+                256,
+                token_code_hash.clone(),
+                token_address,
+            )?
+        }
+        VestingAsset::Native { denom } => {
+            if *denom != token {
+                return Err(StdError::generic_err(format!(
+                    "Round {} does not hold token {}",
+                    stage, token
+                )));
+            }
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: env.contract.address.clone(),
+                to_address: recipient.clone(),
+                amount: vec![Coin {
+                    denom: denom.clone(),
+                    amount: Uint128::from(amount),
+                }],
+            })
+        }
+    };
+
+    config.total_withdrawn = Uint128::from(
+        config
+            .total_withdrawn
+            .u128()
+            .checked_add(amount)
+            .ok_or_else(|| StdError::generic_err("Withdrawn amount overflow!"))?,
+    );
+    VestingRound::from_storage(&mut deps.storage).make_config(stage, &config)?;
+    store_withdrawal(
+        &mut deps.storage,
+        stage,
+        token.clone(),
+        amount,
+        &deps.api.canonical_address(&recipient)?,
+        &env.block,
+    )?;
+
+    messages.push(message);
+
+    Ok(HandleResponse {
+        messages,
+        log: [
+            vec![
+                log("event", "emergency_withdraw"),
+                log("status", "success"),
+                log("stage", stage),
+                log("token", token),
+                log("recipient", recipient),
+            ],
+            amount_logs(
+                &deps.storage,
+                vec![
+                    log("amount", amount),
+                    log("total_withdrawn", config.total_withdrawn),
+                ],
+            ),
+        ]
+        .concat(),
+        data: Some(to_binary(&HandleAnswer::EmergencyWithdraw {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+// ================= Utility function ===================
+
+/// Drops `logs` when the contract keeps amounts private, handles then report them in
+/// their encrypted data only
+fn amount_logs<S: ReadonlyStorage>(storage: &S, logs: Vec<LogAttribute>) -> Vec<LogAttribute> {
+    if ReadonlyConfig::from_storage(storage).private_amounts() {
+        vec![]
+    } else {
+        logs
+    }
+}
+
+fn is_admin<S: Storage>(config: &Config<S>, account: &CanonicalAddr) -> StdResult<bool> {
+    let owner = config.contract_owner()?;
+    if &owner != account {
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+fn is_granted_admin<S: Storage>(config: &Config<S>, account: &CanonicalAddr) -> StdResult<bool> {
+    let owner = config.granted_contract_owner()?;
+    if &owner != account {
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+fn check_if_admin<S: Storage>(config: &Config<S>, account: &CanonicalAddr) -> StdResult<()> {
+    if !is_admin(config, account)? {
+        return Err(StdError::generic_err(
+            "This is an admin command. Admin commands can only be run from admin address",
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_if_admin_query<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-) -> StdResult<HumanAddr> {
-    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
-    let contract_owner = config_storage.contract_owner()?;
-    Ok(deps.api.human_address(&contract_owner)?)
+    account: &HumanAddr,
+) -> StdResult<()> {
+    if ReadonlyConfig::from_storage(&deps.storage).contract_owner()?
+        != deps.api.canonical_address(account)?
+    {
+        return Err(StdError::generic_err(
+            "This is an admin query. Admin queries can only be run from admin address",
+        ));
+    }
+
+    Ok(())
 }
 
-fn get_granted_contract_owner<S: Storage, A: Api, Q: Querier>(
+fn check_if_granted_admin<S: Storage>(
+    config: &Config<S>,
+    account: &CanonicalAddr,
+) -> StdResult<()> {
+    if !is_granted_admin(config, account)? {
+        return Err(StdError::generic_err(
+            "This is a granted admin command. Granted admin commands can only be run from granted admin address",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Sums the coins of `denom` attached to the message, rejecting any other denom
+fn sent_funds_of(env: &Env, denom: &str) -> StdResult<u128> {
+    env.message
+        .sent_funds
+        .iter()
+        .try_fold(0u128, |total, coin| {
+            if coin.denom != denom {
+                return Err(StdError::generic_err(format!(
+                    "Unexpected denom {}, this round only accepts {}",
+                    coin.denom, denom
+                )));
+            }
+
+            total
+                .checked_add(coin.amount.u128())
+                .ok_or_else(|| StdError::generic_err("Funded amount overflow!"))
+        })
+}
+
+/// Builds the message paying `amount` of the round asset to `recipient`
+fn payout_msg<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-) -> StdResult<HumanAddr> {
-    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
-    let contract_owner = config_storage.granted_contract_owner()?;
-    Ok(deps.api.human_address(&contract_owner)?)
+    env: &Env,
+    config: &VestingRoundState,
+    recipient: HumanAddr,
+    amount: u128,
+    msg: Option<Binary>,
+    memo: &str,
+) -> StdResult<CosmosMsg> {
+    // Payouts with a message are sent, the token calls the `Receive` hook of the
+    // recipient with the code hash it registered
+    match (&config.asset, msg) {
+        (
+            VestingAsset::Snip20 {
+                token_address,
+                token_code_hash,
+            },
+            None,
+        ) if config.custody => snip20::transfer_msg(
+            recipient,
+            Uint128::from(amount),
+            Some(String::from(memo)),
+            None,
+            256,
+            token_code_hash.clone(),
+            deps.api.human_address(token_address)?,
+        ),
+        (
+            VestingAsset::Snip20 {
+                token_address,
+                token_code_hash,
+            },
+            None,
+        ) => snip20::transfer_from_msg(
+            deps.api.human_address(&config.distribution)?,
+            recipient,
+            Uint128::from(amount),
+            Some(String::from(memo)),
+            None,
+            256,
+            token_code_hash.clone(),
+            deps.api.human_address(token_address)?,
+        ),
+        (
+            VestingAsset::Snip20 {
+                token_address,
+                token_code_hash,
+            },
+            Some(msg),
+        ) if config.custody => snip20::send_msg(
+            recipient,
+            Uint128::from(amount),
+            Some(msg),
+            Some(String::from(memo)),
+            None,
+            256,
+            token_code_hash.clone(),
+            deps.api.human_address(token_address)?,
+        ),
+        (
+            VestingAsset::Snip20 {
+                token_address,
+                token_code_hash,
+            },
+            Some(msg),
+        ) => snip20::send_from_msg(
+            deps.api.human_address(&config.distribution)?,
+            recipient,
+            Uint128::from(amount),
+            Some(msg),
+            Some(String::from(memo)),
+            None,
+            256,
+            token_code_hash.clone(),
+            deps.api.human_address(token_address)?,
+        ),
+        (VestingAsset::Native { denom }, None) => Ok(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: recipient,
+            amount: vec![Coin {
+                denom: denom.clone(),
+                amount: Uint128::from(amount),
+            }],
+        })),
+        (VestingAsset::Native { .. }, Some(_)) => Err(StdError::generic_err(
+            "Claim messages can only be sent with SNIP-20 tokens!",
+        )),
+    }
+}
+
+/// Returns the funds the round can draw on outside of its escrow accounting, and the
+/// expiration of the distribution allowance when claims are paid with `transfer_from`.
+///
+/// The contract viewing key only unlocks the contract's own token balance, so rounds that
+/// are not in custody are limited by the allowance granted by the distribution wallet, which
+/// the token answers for the spender's key as well. A wallet holding less than its allowance
+/// makes the `transfer_from` fail, and with it the whole claim.
+fn round_funds<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    contract_address: &HumanAddr,
+    stage: u128,
+    config: &VestingRoundState,
+) -> StdResult<(u128, Option<u64>)> {
+    match &config.asset {
+        VestingAsset::Native { denom } => {
+            let balance = deps
+                .querier
+                .query_balance(contract_address.clone(), denom)?
+                .amount;
+            Ok((balance.u128(), None))
+        }
+        VestingAsset::Snip20 {
+            token_address,
+            token_code_hash,
+        } => {
+            let viewing_key = ReadonlyConfig::from_storage(&deps.storage).contract_viewing_key()?;
+            let token_address = deps.api.human_address(token_address)?;
+
+            if config.custody {
+                let balance = snip20::balance_query(
+                    &deps.querier,
+                    contract_address.clone(),
+                    viewing_key.0,
+                    256,
+                    token_code_hash.clone(),
+                    token_address,
+                )?;
+                // Staked tokens come back from the staking contract before they are paid
+                let staked = match config.staking {
+                    Some(_) => read_staking_state(&deps.storage, stage)?
+                        .total_staked
+                        .u128(),
+                    None => 0,
+                };
+                Ok((balance.amount.u128() + staked, None))
+            } else {
+                let allowance = snip20::allowance_query(
+                    &deps.querier,
+                    deps.api.human_address(&config.distribution)?,
+                    contract_address.clone(),
+                    viewing_key.0,
+                    256,
+                    token_code_hash.clone(),
+                    token_address,
+                )?;
+                Ok((allowance.allowance.u128(), allowance.expiration))
+            }
+        }
+    }
+}
+
+/// Pays `amount` to `recipient`, less the claim fee of the round which goes to the fee
+/// recipient. Returns the fee along with the messages.
+fn claim_payout_msgs<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    config: &VestingRoundState,
+    recipient: HumanAddr,
+    amount: u128,
+    msg: Option<Binary>,
+    memo: &str,
+) -> StdResult<(Vec<CosmosMsg>, u128)> {
+    // Rounds in custody can only pay out what was deposited for them, the fee included
+    if config.custody && config.escrow_balance() < amount {
+        return Err(StdError::generic_err(
+            ContractError::InsufficientFunds {
+                balance: Uint128::from(config.escrow_balance()),
+                amount: Uint128::from(amount),
+            }
+            .to_string(),
+        ));
+    }
+
+    let fee = match &config.fee {
+        Some(fee) => fee.fee_of(amount),
+        None => 0,
+    };
+
+    let mut msgs = vec![payout_msg(
+        deps,
+        env,
+        config,
+        recipient,
+        amount - fee,
+        msg,
+        memo,
+    )?];
+    if let (Some(claim_fee), true) = (&config.fee, fee > 0) {
+        msgs.push(payout_msg(
+            deps,
+            env,
+            config,
+            deps.api.human_address(&claim_fee.recipient)?,
+            fee,
+            None,
+            "Claim fee",
+        )?);
+    }
+
+    Ok((msgs, fee))
+}
+
+/// Claims the rewards the staking contract earned for the escrow of a round, and spreads
+/// them over the staked tokens
+fn harvest_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    staking: &StakingContract,
+    staking_state: &mut StakingState,
+) -> StdResult<Option<CosmosMsg>> {
+    let staking_address = deps.api.human_address(&staking.address)?;
+    let viewing_key = ReadonlyConfig::from_storage(&deps.storage).contract_viewing_key()?;
+    let StakingQueryAnswer::Rewards { rewards } = StakingQueryMsg::Rewards {
+        address: env.contract.address.clone(),
+        key: viewing_key.0,
+    }
+    .query(
+        &deps.querier,
+        staking.code_hash.clone(),
+        staking_address.clone(),
+    )?;
+
+    if rewards.is_zero() {
+        return Ok(None);
+    }
+    staking_state.distribute(rewards.u128())?;
+
+    Ok(Some(StakingHandleMsg::ClaimRewards {}.to_cosmos_msg(
+        staking.code_hash.clone(),
+        staking_address,
+        None,
+    )?))
+}
+
+/// Pays staking rewards, which the contract holds apart from the round escrow
+fn reward_payout_msg<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &VestingRoundState,
+    recipient: HumanAddr,
+    amount: u128,
+    msg: Option<Binary>,
+) -> StdResult<CosmosMsg> {
+    let (token_address, token_code_hash) = match &config.asset {
+        VestingAsset::Snip20 {
+            token_address,
+            token_code_hash,
+        } => (
+            deps.api.human_address(token_address)?,
+            token_code_hash.clone(),
+        ),
+        VestingAsset::Native { .. } => {
+            return Err(StdError::generic_err(
+                "Only SNIP-20 rounds in custody can stake their escrow!",
+            ))
+        }
+    };
+
+    let memo = Some("Pay staking rewards".to_string());
+    match msg {
+        Some(msg) => snip20::send_msg(
+            recipient,
+            Uint128::from(amount),
+            Some(msg),
+            memo,
+            None,
+            256,
+            token_code_hash,
+            token_address,
+        ),
+        None => snip20::transfer_msg(
+            recipient,
+            Uint128::from(amount),
+            memo,
+            None,
+            256,
+            token_code_hash,
+            token_address,
+        ),
+    }
+}
+
+/// Ensures the round asset can cover `amount` before payout messages are emitted
+fn check_solvency<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    stage: u128,
+    config: &VestingRoundState,
+    amount: u128,
+) -> StdResult<()> {
+    let (balance, expiration) = round_funds(deps, &env.contract.address, stage, config)?;
+
+    // An expired allowance cannot be spent at all
+    let balance = match expiration {
+        Some(expiration) if expiration <= env.block.time => 0,
+        _ => balance,
+    };
+
+    if balance < amount {
+        return Err(StdError::generic_err(
+            ContractError::InsufficientFunds {
+                balance: Uint128::from(balance),
+                amount: Uint128::from(amount),
+            }
+            .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_round_metadata(metadata: &RoundMetadata) -> StdResult<()> {
+    if let Some(name) = &metadata.name {
+        if name.is_empty() || name.len() > MAX_ROUND_NAME_LEN {
+            return Err(StdError::generic_err(format!(
+                "Round name must be 1 to {} bytes long",
+                MAX_ROUND_NAME_LEN
+            )));
+        }
+    }
+    for text in [&metadata.description, &metadata.uri]
+        .iter()
+        .copied()
+        .flatten()
+    {
+        if text.len() > MAX_ROUND_TEXT_LEN {
+            return Err(StdError::generic_err(format!(
+                "Round description and uri must be at most {} bytes long",
+                MAX_ROUND_TEXT_LEN
+            )));
+        }
+    }
+    if let Some(content_hash) = &metadata.content_hash {
+        let mut hash_buf = [0u8; 32];
+        if hex::decode_to_slice(content_hash, &mut hash_buf).is_err() {
+            return Err(StdError::generic_err(
+                "Round content hash must be a hex encoded SHA-256 hash",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn validate_round_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    distribution: Option<HumanAddr>,
+    token_address: Option<HumanAddr>,
+    token_code_hash: Option<String>,
+    denom: Option<String>,
+    custody: Option<bool>,
+    total_allocation: Option<Uint128>,
+    epoch: Option<Duration>,
+    epoch_anchor: Option<EpochAnchor>,
+    is_paused: Option<bool>,
+    import_positions: Option<bool>,
+    merkle_root: Option<String>,
+    allocator: Option<Allocator>,
+    explicit: Option<bool>,
+    fee: Option<RoundFee>,
+    staking: Option<RoundStaking>,
+) -> StdResult<VestingRoundState> {
+    let is_paused = is_paused.unwrap_or(false);
+
+    let allocation = match (merkle_root, allocator, explicit.unwrap_or(false)) {
+        (Some(merkle_root), None, false) => {
+            // check merkle root length
+            let mut root_buf: [u8; 32] = [0; 32];
+            let is_valid_merkle_root = hex::decode_to_slice(&merkle_root, &mut root_buf);
+
+            if is_valid_merkle_root.is_err() {
+                return Err(StdError::generic_err("Invalid merkle tree validation!"));
+            }
+            AllocationMode::Merkle { merkle_root }
+        }
+        (None, Some(allocator), false) => {
+            validate_public_key(&allocator.algorithm, &allocator.public_key)?;
+            AllocationMode::Signature { allocator }
+        }
+        (None, None, true) => AllocationMode::Explicit {
+            total_allocated: Uint128::zero(),
+        },
+        _ => {
+            return Err(StdError::generic_err(
+                "A vesting round needs exactly one of a merkle root, an allocator key or explicit allocations!",
+            ))
+        }
+    };
+
+    // Specify distribution address
+    let distribution_addr = deps
+        .api
+        .canonical_address(&distribution.unwrap_or_else(|| env.message.sender.clone()))?;
+
+    let asset = match (token_address, token_code_hash, denom) {
+        (Some(token_address), Some(token_code_hash), None) => Ok(VestingAsset::Snip20 {
+            token_address: deps.api.canonical_address(&token_address)?,
+            token_code_hash,
+        }),
+        (None, None, Some(denom)) => {
+            if denom.is_empty() {
+                Err(StdError::generic_err("Not a valid denom!"))
+            } else {
+                Ok(VestingAsset::Native { denom })
+            }
+        }
+        (_, _, Some(_)) => Err(StdError::generic_err(
+            "A vesting round distributes either a token or a native denom, not both!",
+        )),
+        (Some(_), None, None) => Err(StdError::generic_err("Not a valid token code hash!")),
+        (None, Some(_), None) => Err(StdError::generic_err("Not a valid token address!")),
+        _ => Err(StdError::generic_err(
+            "Invalid both token address and token code hash!",
+        )),
+    }?;
+
+    // Native coins can only be paid from the contract's own balance
+    let custody = match asset {
+        VestingAsset::Native { .. } => true,
+        VestingAsset::Snip20 { .. } => custody.unwrap_or(false),
+    };
+
+    // Rounds vest daily unless they say otherwise
+    let epoch = epoch.unwrap_or(Duration::Time(ONE_DAY_IN_SECONDS));
+    if epoch_length(&epoch) == 0 {
+        return Err(StdError::generic_err("Vesting epoch can't be empty!"));
+    }
+
+    let staking = match staking {
+        Some(staking) => {
+            if !custody || matches!(asset, VestingAsset::Native { .. }) {
+                return Err(StdError::generic_err(
+                    "Only SNIP-20 rounds in custody can stake their escrow!",
+                ));
+            }
+
+            // Rewards are reported for the whole contract, so they can't be shared by rounds
+            let address = deps.api.canonical_address(&staking.address)?;
+            let rounds = ReadonlyVestingRound::from_storage(&deps.storage);
+            for stage in 1..ReadonlyConfig::from_storage(&deps.storage).current_stage() {
+                if matches!(rounds.config_by_stage(stage)?.staking, Some(round_staking) if round_staking.address == address)
+                {
+                    return Err(StdError::generic_err(format!(
+                        "Staking contract is already used by round {}",
+                        stage
+                    )));
+                }
+            }
+
+            Some(StakingContract {
+                address,
+                code_hash: staking.code_hash,
+            })
+        }
+        None => None,
+    };
+
+    // A zero rate is the same as no fee at all
+    let fee = match fee {
+        Some(fee) if fee.rate_bps > MAX_CLAIM_FEE_BPS => {
+            return Err(StdError::generic_err(format!(
+                "Claim fee can be at most {} basis points!",
+                MAX_CLAIM_FEE_BPS
+            )))
+        }
+        Some(fee) if fee.rate_bps > 0 => Some(ClaimFee {
+            rate_bps: fee.rate_bps,
+            recipient: deps.api.canonical_address(&fee.recipient)?,
+        }),
+        _ => None,
+    };
+
+    let config = VestingRoundState {
+        distribution: distribution_addr,
+        created_at: env.block.time,
+        allocation,
+        is_paused,
+        asset,
+        custody,
+        total_allocation,
+        total_funded: Uint128::zero(),
+        total_claimed: Uint128::zero(),
+        total_withdrawn: Uint128::zero(),
+        epoch,
+        epoch_anchor: epoch_anchor.unwrap_or(EpochAnchor::Calendar),
+        fee,
+        staking,
+        activated: !import_positions.unwrap_or(false),
+    };
+
+    Ok(config)
+}
+
+// ================= Query ===================
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetConfig { stage } => to_binary(&get_config_by_stage(deps, stage.0)?),
+        QueryMsg::GetCurrentStage {} => to_binary(&get_current_stage(deps)?),
+        QueryMsg::VestingRounds { page, page_size } => {
+            to_binary(&get_vesting_rounds(deps, page.unwrap_or(0), page_size)?)
+        }
+        QueryMsg::EscrowBalance { stage } => to_binary(&get_escrow_balance(deps, stage.u128())?),
+        QueryMsg::RoundFundingStatus { stage } => {
+            to_binary(&get_round_funding_status(deps, stage.u128())?)
+        }
+        QueryMsg::RoundStats { stage } => to_binary(&get_round_stats(deps, stage.u128())?),
+        QueryMsg::StakingStatus { stage } => to_binary(&get_staking_status(deps, stage.u128())?),
+        QueryMsg::ContractOwner {} => to_binary(&get_contract_owner(deps)?),
+        QueryMsg::GrantedContractOwner {} => to_binary(&get_granted_contract_owner(deps)?),
+        QueryMsg::ContractStatus {} => to_binary(&get_contract_status(deps)),
+        QueryMsg::Allocations {
+            stage,
+            page,
+            page_size,
+        } => to_binary(&get_allocations_by_stage(
+            deps,
+            stage.u128(),
+            page.unwrap_or(0),
+            page_size,
+        )?),
+        QueryMsg::EmergencyWithdrawals {
+            stage,
+            page,
+            page_size,
+        } => to_binary(&get_emergency_withdrawals(
+            deps,
+            stage.u128(),
+            page.unwrap_or(0),
+            page_size,
+        )?),
+        QueryMsg::UnlockSchedule {
+            stage,
+            amount,
+            tge,
+            start_at,
+            cliff,
+            duration,
+            start_after,
+            limit,
+        } => to_binary(&get_unlock_schedule(
+            deps,
+            stage.u128(),
+            amount.u128(),
+            tge.u128(),
+            start_at,
+            cliff,
+            duration,
+            start_after,
+            limit,
+        )?),
+        _ => viewing_keys_queries(deps, msg),
+    }
+}
+
+fn viewing_keys_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let (addresses, key) = msg.get_validation_params();
+
+    for address in addresses {
+        let canonical_addr = deps.api.canonical_address(address)?;
+
+        let expected_key = read_viewing_key(&deps.storage, &canonical_addr);
+
+        let is_valid_key = match expected_key {
+            Some(expected_key) => key.check_viewing_key(expected_key.as_slice()),
+            None => {
+                // Checking the key will take significant time. We don't want to exit immediately if it isn't set
+                // in a way which will allow to time the command and determine if a viewing key doesn't exist
+                key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+                false
+            }
+        };
+
+        if is_valid_key {
+            return match msg {
+                QueryMsg::ClaimHistory {
+                    address,
+                    page,
+                    page_size,
+                    ..
+                } => to_binary(&get_claim_history(
+                    deps,
+                    &address,
+                    page.unwrap_or(0),
+                    page_size,
+                )?),
+                QueryMsg::ContractViewingKey { address, .. } => {
+                    to_binary(&get_contract_viewing_key(deps, &address)?)
+                }
+                QueryMsg::Reconcile {
+                    address,
+                    stage,
+                    start_after,
+                    limit,
+                    claimed_before,
+                    ..
+                } => to_binary(&get_reconcile(
+                    deps,
+                    &address,
+                    stage.u128(),
+                    start_after,
+                    limit,
+                    claimed_before.map_or(0, |claimed| claimed.u128()),
+                )?),
+                QueryMsg::ExportPositions {
+                    address,
+                    stage,
+                    start_after,
+                    limit,
+                    ..
+                } => to_binary(&get_export_positions(
+                    deps,
+                    &address,
+                    stage.u128(),
+                    start_after,
+                    limit,
+                )?),
+                QueryMsg::PositionUnlockSchedule {
+                    address,
+                    stage,
+                    grant,
+                    start_after,
+                    limit,
+                    ..
+                } => to_binary(&get_position_unlock_schedule(
+                    deps,
+                    &address,
+                    stage.u128(),
+                    grant.unwrap_or(0),
+                    start_after,
+                    limit,
+                )?),
+                _ => panic!("This query type does not require authentication"),
+            };
+        }
+    }
+
+    Err(StdError::generic_err(
+        "Wrong viewing key for this address or viewing key not set",
+    ))
+}
+
+fn get_config_by_stage<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+) -> StdResult<VestingRoundResponse> {
+    let config_storage = ReadonlyVestingRound::from_storage(&deps.storage);
+    let config = config_storage.config_by_stage(stage)?;
+    let (token_address, denom) = match config.asset {
+        VestingAsset::Snip20 { token_address, .. } => {
+            (Some(deps.api.human_address(&token_address)?), None)
+        }
+        VestingAsset::Native { denom } => (None, Some(denom)),
+    };
+    let (merkle_root, allocator, total_allocated) = match config.allocation {
+        AllocationMode::Merkle { merkle_root } => (Some(merkle_root), None, None),
+        AllocationMode::Signature { allocator } => (None, Some(allocator), None),
+        AllocationMode::Explicit { total_allocated } => (None, None, Some(total_allocated)),
+    };
+    let fee = match config.fee {
+        Some(fee) => Some(RoundFee {
+            rate_bps: fee.rate_bps,
+            recipient: deps.api.human_address(&fee.recipient)?,
+        }),
+        None => None,
+    };
+    let staking = match config.staking {
+        Some(staking) => Some(RoundStaking {
+            address: deps.api.human_address(&staking.address)?,
+            code_hash: staking.code_hash,
+        }),
+        None => None,
+    };
+
+    Ok(VestingRoundResponse {
+        stage: Uint128::from(stage),
+        total_claimed: config.total_claimed,
+        total_funded: config.total_funded,
+        total_withdrawn: config.total_withdrawn,
+        merkle_root,
+        allocator,
+        total_allocated,
+        metadata: read_round_metadata(&deps.storage, stage)?,
+        token_address,
+        denom,
+        custody: config.custody,
+        epoch: config.epoch,
+        epoch_anchor: config.epoch_anchor,
+        is_paused: config.is_paused,
+        activated: config.activated,
+        fee,
+        staking,
+        created_at: config.created_at,
+    })
+}
+
+fn get_vesting_rounds<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    page: u32,
+    page_size: u32,
+) -> StdResult<VestingRoundsResponse> {
+    let current_stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+    let (skip, take) = page_bounds(page, page_size)?;
+
+    let rounds: StdResult<Vec<VestingRoundResponse>> = (1..=current_stage)
+        .rev()
+        .skip(skip)
+        .take(take)
+        .map(|stage| get_config_by_stage(deps, stage))
+        .collect();
+
+    Ok(VestingRoundsResponse {
+        rounds: rounds?,
+        total: current_stage as u64,
+    })
+}
+
+fn get_round_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+) -> StdResult<RoundStatsResponse> {
+    // Fails for stages that were never registered
+    ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let stats = read_round_stats(&deps.storage, stage)?;
+
+    Ok(RoundStatsResponse {
+        stage: Uint128::from(stage),
+        positions: stats.positions,
+        fully_vested_positions: stats.fully_vested_positions,
+        tge_paid: stats.tge_paid,
+        linear_paid: stats.linear_paid,
+        claim_count: stats.claim_count,
+        first_claim_at: stats.first_claim_at,
+        last_claim_at: stats.last_claim_at,
+        imported_amount: stats.imported_amount,
+        imported_claimed: stats.imported_claimed,
+        fees_paid: stats.fees_paid,
+    })
+}
+
+fn get_staking_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+) -> StdResult<StakingStatusResponse> {
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let staking = config
+        .staking
+        .ok_or_else(|| StdError::generic_err("This vesting round does not stake its escrow"))?;
+    let state = read_staking_state(&deps.storage, stage)?;
+
+    Ok(StakingStatusResponse {
+        stage: Uint128::from(stage),
+        staking: RoundStaking {
+            address: deps.api.human_address(&staking.address)?,
+            code_hash: staking.code_hash,
+        },
+        total_staked: state.total_staked,
+        reward_per_token: state.reward_per_token,
+        rewards_harvested: state.rewards_harvested,
+        rewards_paid: state.rewards_paid,
+        unassigned_rewards: state.unassigned_rewards,
+        rewards_swept: state.rewards_swept,
+    })
+}
+
+fn get_escrow_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+) -> StdResult<EscrowBalanceResponse> {
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+
+    Ok(EscrowBalanceResponse {
+        stage: Uint128::from(stage),
+        total_funded: config.total_funded,
+        total_claimed: config.total_claimed,
+        total_withdrawn: config.total_withdrawn,
+        balance: Uint128::from(config.escrow_balance()),
+    })
+}
+
+fn get_current_stage<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Uint128> {
+    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
+    let current_stage = Uint128::from(config_storage.current_stage());
+    Ok(current_stage)
+}
+
+fn get_contract_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<HumanAddr> {
+    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
+    let contract_owner = config_storage.contract_owner()?;
+    deps.api.human_address(&contract_owner)
+}
+
+fn get_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> ContractStatusResponse {
+    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
+    ContractStatusResponse {
+        status: config_storage.contract_status(),
+    }
+}
+
+fn get_granted_contract_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<HumanAddr> {
+    let config_storage = ReadonlyConfig::from_storage(&deps.storage);
+    let contract_owner = config_storage.granted_contract_owner()?;
+    deps.api.human_address(&contract_owner)
+}
+
+fn get_round_funding_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+) -> StdResult<RoundFundingStatusResponse> {
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let contract_address = deps
+        .api
+        .human_address(&ReadonlyConfig::from_storage(&deps.storage).contract_address()?)?;
+
+    let (funds, allowance_expiration) = round_funds(deps, &contract_address, stage, &config)?;
+
+    // Deposits held for other rounds of the same asset are not available to this one
+    let available = if config.custody {
+        funds.min(config.escrow_balance())
+    } else {
+        funds
+    };
+
+    // Imported positions were partly paid out by the contract they come from
+    let imported_claimed = read_round_stats(&deps.storage, stage)?.imported_claimed;
+    let outstanding = config.total_allocation.map(|total_allocation| {
+        total_allocation
+            .u128()
+            .saturating_sub(config.total_claimed.u128())
+            .saturating_sub(imported_claimed.u128())
+    });
+
+    Ok(RoundFundingStatusResponse {
+        stage: Uint128::from(stage),
+        total_allocation: config.total_allocation,
+        total_claimed: config.total_claimed,
+        outstanding: outstanding.map(Uint128::from),
+        available: Uint128::from(available),
+        allowance_expiration,
+        is_covered: outstanding.map(|outstanding| available >= outstanding),
+    })
+}
+
+fn get_contract_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+) -> StdResult<ContractViewingKeyResponse> {
+    check_if_admin_query(deps, account)?;
+
+    Ok(ContractViewingKeyResponse {
+        key: ReadonlyConfig::from_storage(&deps.storage)
+            .contract_viewing_key()?
+            .0,
+    })
+}
+
+fn get_reconcile<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    stage: u128,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    claimed_before: u128,
+) -> StdResult<ReconcileResponse> {
+    check_if_admin_query(deps, account)?;
+
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let epoch_length = epoch_length(&config.epoch);
+    let (positions, total_positions) =
+        get_indexed_positions(&deps.storage, stage, start_after, limit)?;
+
+    let mut discrepancies = vec![];
+    let mut claimed_through = claimed_before;
+    for position in positions.iter() {
+        let address = deps.api.human_address(&position.user)?;
+        match read_user_vesting_stats(&deps.storage, &position.user, stage, position.grant)? {
+            Some(stats) => {
+                claimed_through += stats.total_claimed.u128();
+                discrepancies.extend(check_position(
+                    &address,
+                    &stats,
+                    epoch_length,
+                    &config.epoch_anchor,
+                )?);
+            }
+            None => discrepancies.push(Discrepancy::of_position(
+                DiscrepancyKind::MissingPosition,
+                &address,
+                position.grant,
+                0,
+                0,
+            )),
+        }
+    }
+
+    let last = positions
+        .last()
+        .map(|position| position.index)
+        .or(start_after);
+    let is_last_page = match last {
+        Some(index) => index + 1 >= total_positions,
+        None => true,
+    };
+    // Imported positions carry what the previous contract paid, which this round never did
+    let expected_claimed = config.total_claimed.u128()
+        + read_round_stats(&deps.storage, stage)?
+            .imported_claimed
+            .u128();
+    if is_last_page && claimed_through != expected_claimed {
+        discrepancies.push(Discrepancy {
+            kind: DiscrepancyKind::RoundTotalMismatch,
+            address: None,
+            grant: None,
+            expected: Uint128::from(expected_claimed),
+            actual: Uint128::from(claimed_through),
+        });
+    }
+
+    Ok(ReconcileResponse {
+        stage: Uint128::from(stage),
+        total_positions,
+        last,
+        claimed_through: Uint128::from(claimed_through),
+        discrepancies,
+    })
+}
+
+fn get_export_positions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    stage: u128,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ExportPositionsResponse> {
+    check_if_admin_query(deps, account)?;
+
+    // Fails for stages that were never registered
+    ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+    let (indexed, total) = get_indexed_positions(&deps.storage, stage, start_after, limit)?;
+
+    let mut positions = vec![];
+    for position in indexed.iter() {
+        let stats = read_user_vesting_stats(&deps.storage, &position.user, stage, position.grant)?
+            .ok_or_else(|| StdError::generic_err("Position index is corrupted"))?;
+        positions.push(PositionRecord::from_stats(
+            deps.api.human_address(&position.user)?,
+            &stats,
+        ));
+    }
+
+    Ok(ExportPositionsResponse {
+        stage: Uint128::from(stage),
+        positions,
+        total,
+        last: indexed
+            .last()
+            .map(|position| position.index)
+            .or(start_after),
+    })
+}
+
+fn get_claim_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<ClaimHistoryResponse> {
+    let address = deps.api.canonical_address(account)?;
+    let (claims, total) = get_claims(&deps.api, &deps.storage, &address, page, page_size)?;
+
+    Ok(ClaimHistoryResponse { claims, total })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn get_unlock_schedule<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+    amount: u128,
+    tge: u128,
+    start_at: u64,
+    cliff: u64,
+    duration: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<UnlockScheduleResponse> {
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+
+    let tge_amount = calc_tge_amount(amount, tge)?;
+    let linear_amount = amount - tge_amount;
+    let (events, total_epochs) = unlock_events(
+        tge_amount,
+        linear_amount,
+        start_at,
+        cliff,
+        duration,
+        &config.epoch,
+        &config.epoch_anchor,
+        start_after,
+        limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE),
+    )?;
+
+    Ok(UnlockScheduleResponse {
+        stage: Uint128::from(stage),
+        tge_amount: Uint128::from(tge_amount),
+        linear_amount: Uint128::from(linear_amount),
+        total_claimed: None,
+        last: last_epoch_index(&events).or(start_after),
+        events,
+        total_epochs,
+    })
+}
+
+fn get_position_unlock_schedule<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    stage: u128,
+    grant: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<UnlockScheduleResponse> {
+    let address = deps.api.canonical_address(account)?;
+    let stats = read_user_vesting_stats(&deps.storage, &address, stage, grant)?
+        .ok_or_else(|| StdError::generic_err("No vesting position for this address"))?;
+    let config = ReadonlyVestingRound::from_storage(&deps.storage).config_by_stage(stage)?;
+
+    let (events, total_epochs) = unlock_events(
+        stats.tge_amount.u128(),
+        stats.total_amount.u128(),
+        stats.start_vesting_epoch,
+        stats.cliff,
+        stats.vesting_duration,
+        &config.epoch,
+        &config.epoch_anchor,
+        start_after,
+        limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE),
+    )?;
+
+    Ok(UnlockScheduleResponse {
+        stage: Uint128::from(stage),
+        tge_amount: stats.tge_amount,
+        linear_amount: stats.total_amount,
+        total_claimed: Some(stats.total_claimed),
+        last: last_epoch_index(&events).or(start_after),
+        events,
+        total_epochs,
+    })
+}
+
+/// Epoch index of the last `Epoch` event of a page
+fn last_epoch_index(events: &[UnlockEvent]) -> Option<u64> {
+    events.iter().rev().find_map(|event| event.epoch_index)
+}
+
+fn get_allocations_by_stage<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+    page: u32,
+    page_size: u32,
+) -> StdResult<AllocationsResponse> {
+    let (allocations, total) = get_allocations(&deps.api, &deps.storage, stage, page, page_size)?;
+
+    Ok(AllocationsResponse { allocations, total })
+}
+
+fn get_emergency_withdrawals<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    stage: u128,
+    page: u32,
+    page_size: u32,
+) -> StdResult<EmergencyWithdrawalsResponse> {
+    let (withdrawals, total) = get_withdrawals(&deps.api, &deps.storage, stage, page, page_size)?;
+
+    Ok(EmergencyWithdrawalsResponse { withdrawals, total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positions::PositionStatus;
+    use crate::state::{RoundCategory, SignatureAlgorithm, USER_VESTING_STATS_PREFIX};
+    use crate::vesting::unlock_schedule::UnlockEventKind;
+    use crate::withdrawals::WithdrawalTx;
+    use cosmwasm_std::testing::*;
+    use cosmwasm_std::{
+        from_binary, from_slice, BlockInfo, Empty, QuerierResult, QueryRequest, SystemError,
+        WasmMsg, WasmQuery,
+    };
+    use cosmwasm_storage::Bucket;
+    use serde::{Deserialize, Serialize};
+    use sha2::Digest;
+    use std::collections::HashMap;
+
+    // Helper functions
+
+    const START_AT: u64 = 1_600_000_000;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum TokenQueryMsg {
+        Balance {
+            address: HumanAddr,
+            key: String,
+        },
+        Allowance {
+            owner: HumanAddr,
+            spender: HumanAddr,
+            key: String,
+        },
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    enum TokenQueryAnswer {
+        Balance {
+            amount: Uint128,
+        },
+        Allowance {
+            spender: HumanAddr,
+            owner: HumanAddr,
+            allowance: Uint128,
+            expiration: Option<u64>,
+        },
+        ViewingKeyError {
+            msg: String,
+        },
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum TokenHandleMsg {
+        Send {
+            recipient: HumanAddr,
+            amount: Uint128,
+        },
+    }
+
+    const STAKING_ADDR: &str = "staking";
+
+    /// Staking contract holding the escrow of a round, it earns `rewards` until they are claimed
+    #[derive(Default)]
+    struct MockStaking {
+        staked: u128,
+        rewards: u128,
+    }
+
+    /// Answers SNIP-20 balance and allowance queries on top of the bank mock querier, and the
+    /// reward queries of the mock staking contract
+    struct TokenQuerier {
+        base: MockQuerier,
+        balances: HashMap<HumanAddr, u128>,
+        allowances: HashMap<HumanAddr, (u128, Option<u64>)>,
+        /// Viewing keys set on the token, queries with any other key are rejected
+        viewing_keys: HashMap<HumanAddr, String>,
+        staking: MockStaking,
+    }
+
+    impl TokenQuerier {
+        fn new(base: MockQuerier) -> Self {
+            let mut querier = Self {
+                base,
+                balances: HashMap::new(),
+                allowances: HashMap::new(),
+                viewing_keys: HashMap::new(),
+                staking: MockStaking::default(),
+            };
+            querier
+                .allowances
+                .insert(HumanAddr("distribution".to_string()), (1_000_000_000, None));
+            querier
+                .balances
+                .insert(HumanAddr(MOCK_CONTRACT_ADDR.to_string()), 1_000_000_000);
+            querier.viewing_keys.insert(
+                HumanAddr("distribution".to_string()),
+                "distribution_key".to_string(),
+            );
+            querier
+        }
+
+        fn is_key_of(&self, address: &HumanAddr, key: &str) -> bool {
+            self.viewing_keys.get(address).map(String::as_str) == Some(key)
+        }
+
+        /// Runs the messages a handle sent to the staking contract, directly or through the token
+        fn execute_staking(&mut self, messages: &[CosmosMsg]) {
+            for message in messages {
+                let (contract_addr, msg) = match message {
+                    CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr, msg, ..
+                    }) => (contract_addr.as_str(), msg),
+                    _ => continue,
+                };
+                if contract_addr == STAKING_ADDR {
+                    match from_binary(msg).unwrap() {
+                        StakingHandleMsg::Unstake { amount } => {
+                            self.staking.staked -= amount.u128()
+                        }
+                        StakingHandleMsg::ClaimRewards {} => self.staking.rewards = 0,
+                        StakingHandleMsg::SetViewingKey { .. } => {}
+                    }
+                } else if let Ok(TokenHandleMsg::Send { recipient, amount }) = from_binary(msg) {
+                    if recipient.as_str() == STAKING_ADDR {
+                        self.staking.staked += amount.u128();
+                    }
+                }
+            }
+        }
+    }
+
+    impl Querier for TokenQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = match from_slice(bin_request) {
+                Ok(v) => v,
+                Err(e) => {
+                    return Err(SystemError::InvalidRequest {
+                        error: format!("Parsing query request: {}", e),
+                        request: bin_request.into(),
+                    })
+                }
+            };
+
+            match request {
+                QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr, msg, ..
+                }) if contract_addr.as_str() == STAKING_ADDR => {
+                    let StakingQueryMsg::Rewards { .. } = from_binary(&msg).unwrap();
+                    Ok(to_binary(&StakingQueryAnswer::Rewards {
+                        rewards: Uint128(self.staking.rewards),
+                    }))
+                }
+                QueryRequest::Wasm(WasmQuery::Smart { msg, .. }) => {
+                    let wrong_key = TokenQueryAnswer::ViewingKeyError {
+                        msg: "Wrong viewing key for this address or viewing key not set"
+                            .to_string(),
+                    };
+                    let answer = match from_binary(&msg).unwrap() {
+                        TokenQueryMsg::Balance { address, key }
+                            if self.is_key_of(&address, &key) =>
+                        {
+                            TokenQueryAnswer::Balance {
+                                amount: Uint128(self.balances.get(&address).copied().unwrap_or(0)),
+                            }
+                        }
+                        TokenQueryMsg::Balance { .. } => wrong_key,
+                        TokenQueryMsg::Allowance {
+                            owner,
+                            spender,
+                            key,
+                        } if self.is_key_of(&owner, &key) || self.is_key_of(&spender, &key) => {
+                            let (allowance, expiration) =
+                                self.allowances.get(&owner).copied().unwrap_or((0, None));
+                            TokenQueryAnswer::Allowance {
+                                spender,
+                                owner,
+                                allowance: Uint128(allowance),
+                                expiration,
+                            }
+                        }
+                        TokenQueryMsg::Allowance { .. } => wrong_key,
+                    };
+                    Ok(to_binary(&answer))
+                }
+                _ => self.base.raw_query(bin_request),
+            }
+        }
+    }
+
+    fn init_helper() -> Extern<MockStorage, MockApi, TokenQuerier> {
+        init_helper_with_balance(&[])
+    }
+
+    fn init_helper_with_balance(
+        contract_balance: &[Coin],
+    ) -> Extern<MockStorage, MockApi, TokenQuerier> {
+        let deps = mock_dependencies(20, contract_balance);
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: TokenQuerier::new(deps.querier),
+        };
+        let env = mock_env("admin", &[]);
+
+        let init_msg = InitMsg {
+            owner: None,
+            contract_status: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            private_amounts: None,
+        };
+
+        init(&mut deps, env, init_msg).unwrap();
+        let contract_key = ReadonlyConfig::from_storage(&deps.storage)
+            .contract_viewing_key()
+            .unwrap();
+        deps.querier
+            .viewing_keys
+            .insert(HumanAddr(MOCK_CONTRACT_ADDR.to_string()), contract_key.0);
+        deps
+    }
+
+    fn env_at(sender: &str, time: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block = BlockInfo {
+            height: 12_345 + (time - START_AT) / 6,
+            time,
+            chain_id: "cosmos-testnet-14002".to_string(),
+        };
+        env
+    }
+
+    fn leaf_hash(
+        user: &str,
+        stage: u128,
+        amount: u128,
+        tge: u128,
+        start_at: u64,
+        cliff: u64,
+        duration: u64,
+    ) -> [u8; 32] {
+        let user_input = format!(
+            "{}{}{}{}{}{}{}",
+            user, stage, amount, tge, start_at, duration, cliff
+        );
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(sha2::Sha256::digest(user_input.as_bytes()).as_slice());
+        hash
+    }
+
+    /// Fields of `RegisterNewVestingRound`, a SNIP-20 round paid by the distribution wallet
+    /// unless a test overrides them
+    struct RoundMsg {
+        distribution: Option<HumanAddr>,
+        token_address: Option<HumanAddr>,
+        token_code_hash: Option<String>,
+        denom: Option<String>,
+        custody: Option<bool>,
+        total_allocation: Option<Uint128>,
+        epoch: Option<Duration>,
+        epoch_anchor: Option<EpochAnchor>,
+        is_paused: Option<bool>,
+        import_positions: Option<bool>,
+        merkle_root: Option<String>,
+        allocator: Option<Allocator>,
+        explicit: Option<bool>,
+        metadata: Option<RoundMetadata>,
+        fee: Option<RoundFee>,
+        staking: Option<RoundStaking>,
+        padding: Option<String>,
+    }
+
+    impl Default for RoundMsg {
+        fn default() -> Self {
+            Self {
+                distribution: Some(HumanAddr("distribution".to_string())),
+                token_address: Some(HumanAddr("token".to_string())),
+                token_code_hash: Some("token_hash".to_string()),
+                denom: None,
+                custody: None,
+                total_allocation: None,
+                epoch: None,
+                epoch_anchor: None,
+                is_paused: None,
+                import_positions: None,
+                merkle_root: None,
+                allocator: None,
+                explicit: None,
+                metadata: None,
+                fee: None,
+                staking: None,
+                padding: None,
+            }
+        }
+    }
+
+    impl From<RoundMsg> for HandleMsg {
+        fn from(round: RoundMsg) -> Self {
+            HandleMsg::RegisterNewVestingRound {
+                distribution: round.distribution,
+                token_address: round.token_address,
+                token_code_hash: round.token_code_hash,
+                denom: round.denom,
+                custody: round.custody,
+                total_allocation: round.total_allocation,
+                epoch: round.epoch,
+                epoch_anchor: round.epoch_anchor,
+                is_paused: round.is_paused,
+                import_positions: round.import_positions,
+                merkle_root: round.merkle_root,
+                allocator: round.allocator,
+                explicit: round.explicit,
+                metadata: round.metadata,
+                fee: round.fee,
+                staking: round.staking,
+                padding: round.padding,
+            }
+        }
+    }
+
+    /// Registers a round whose merkle tree holds a single leaf, so the proof is empty
+    fn register_round_helper(
+        deps: &mut Extern<MockStorage, MockApi, TokenQuerier>,
+        leaf: [u8; 32],
+    ) -> u128 {
+        let msg = HandleMsg::from(RoundMsg {
+            merkle_root: Some(hex::encode(leaf)),
+            ..RoundMsg::default()
+        });
+        handle(deps, env_at("admin", START_AT), msg).unwrap();
+
+        ReadonlyConfig::from_storage(&deps.storage).current_stage()
+    }
+
+    fn claim_msg(stage: u128, amount: u128, tge: u128, cliff: u64, duration: u64) -> HandleMsg {
+        HandleMsg::Claim {
+            proof: None,
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128::from(amount),
+            tge: Uint128::from(tge),
+            start_at: START_AT,
+            cliff,
+            duration,
+            msg: None,
+            padding: None,
+        }
+    }
+
+    fn extract_error_msg<T>(error: StdResult<T>) -> String {
+        match error {
+            Ok(_) => panic!("An error was expected"),
+            Err(StdError::GenericErr { msg, .. }) => msg,
+            Err(err) => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    // Init tests
+
+    #[test]
+    fn test_init_sanity() {
+        let deps = init_helper();
+        let config = ReadonlyConfig::from_storage(&deps.storage);
+
+        assert_eq!(
+            config.contract_owner().unwrap(),
+            deps.api
+                .canonical_address(&HumanAddr("admin".to_string()))
+                .unwrap()
+        );
+        assert_eq!(config.contract_status(), ContractStatusLevel::NormalRun);
+        assert_eq!(config.claim_count(), 0);
+        assert!(config
+            .contract_viewing_key()
+            .unwrap()
+            .0
+            .starts_with(crate::viewing_key::VIEWING_KEY_PREFIX));
+    }
+
+    // Handle tests
+
+    #[test]
+    fn test_handle_claim() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        // TGE of 10% is paid together with the first daily unlock
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert!(res.log.contains(&log("tge_amount", 1_000)));
+        assert!(res.log.contains(&log("claim_amount", 900)));
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 2),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(error, "Routine claim is already vested!");
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("bob", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(error, "Proof verification failed!");
+    }
+
+    #[test]
+    fn test_handle_claim_final_remainder() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_001, 333, START_AT, 0, 7 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        let mut claimed = 0u128;
+        for time in &[
+            START_AT + 1,
+            START_AT + day,
+            START_AT + 3 * day,
+            START_AT + 30 * day,
+        ] {
+            let res = handle(
+                &mut deps,
+                env_at("alice", *time),
+                claim_msg(stage, 10_001, 333, 0, 7 * day),
+            )
+            .unwrap();
+            let HandleAnswer::Claim {
+                tge_amount,
+                claim_amount,
+                ..
+            } = from_binary(&res.data.unwrap()).unwrap()
+            else {
+                panic!("Unexpected answer")
+            };
+            claimed += tge_amount.u128() + claim_amount.u128();
+        }
+        assert_eq!(claimed, 10_001);
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 31 * day),
+            claim_msg(stage, 10_001, 333, 0, 7 * day),
+        ));
+        assert_eq!(error, "Exceeds maximum claim amount!");
+    }
+
+    #[test]
+    fn test_handle_height_round() {
+        let mut deps = init_helper();
+        let leaf = leaf_hash("alice", 1, 10_000, 0, 1_000, 200, 1_000);
+        let register_msg = |epoch| {
+            HandleMsg::from(RoundMsg {
+                epoch: Some(epoch),
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            })
+        };
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            register_msg(Duration::Height(100)),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        // Block time is irrelevant, only the height moves the schedule forward
+        let claim_at_height = |deps: &mut Extern<MockStorage, MockApi, TokenQuerier>,
+                               height: u64| {
+            let mut env = env_at("alice", START_AT + 1_000 * ONE_DAY_IN_SECONDS);
+            env.block.height = height;
+            let res = handle(
+                deps,
+                env,
+                HandleMsg::Claim {
+                    proof: None,
+                    signature: None,
+                    stage: Uint128::from(stage),
+                    grant: None,
+                    amount: Uint128(10_000),
+                    tge: Uint128(0),
+                    start_at: 1_000,
+                    cliff: 200,
+                    duration: 1_000,
+                    msg: None,
+                    padding: None,
+                },
+            )?;
+            let HandleAnswer::Claim { claim_amount, .. } = from_binary(&res.data.unwrap())? else {
+                panic!("Unexpected answer")
+            };
+            Ok(claim_amount.u128())
+        };
+
+        let error = extract_error_msg(claim_at_height(&mut deps, 999));
+        assert_eq!(error, "Claim time have not started yet!");
+        assert_eq!(claim_at_height(&mut deps, 1_150), Ok(0));
+        assert_eq!(claim_at_height(&mut deps, 1_201), Ok(1_000));
+        assert_eq!(claim_at_height(&mut deps, 1_550), Ok(3_000));
+        assert_eq!(claim_at_height(&mut deps, 5_000), Ok(6_000));
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            register_msg(Duration::Height(0)),
+        ));
+        assert_eq!(error, "Vesting epoch can't be empty!");
+    }
+
+    #[test]
+    fn test_handle_epoch_anchor() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        // One hour before midnight UTC
+        let start_at = 1_600_038_000;
+        let midnight = start_at + 3_600;
+
+        for (stage, epoch_anchor) in &[(1, EpochAnchor::Calendar), (2, EpochAnchor::Grant)] {
+            let leaf = leaf_hash("alice", *stage, 10_000, 0, start_at, 0, 10 * day);
+            handle(
+                &mut deps,
+                env_at("admin", START_AT),
+                HandleMsg::from(RoundMsg {
+                    epoch_anchor: Some(epoch_anchor.clone()),
+                    merkle_root: Some(hex::encode(leaf)),
+                    ..RoundMsg::default()
+                }),
+            )
+            .unwrap();
+        }
+
+        let claim = |deps: &mut Extern<MockStorage, MockApi, TokenQuerier>, stage, time| {
+            let msg = HandleMsg::Claim {
+                proof: None,
+                signature: None,
+                stage: Uint128(stage),
+                grant: None,
+                amount: Uint128(10_000),
+                tge: Uint128(0),
+                start_at,
+                cliff: 0,
+                duration: 10 * day,
+                msg: None,
+                padding: None,
+            };
+            let res = handle(deps, env_at("alice", time), msg)?;
+            let HandleAnswer::Claim { claim_amount, .. } = from_binary(&res.data.unwrap())? else {
+                panic!("Unexpected answer")
+            };
+            Ok(claim_amount.u128())
+        };
+
+        // Calendar epochs roll over at midnight, an hour after the first unlock
+        assert_eq!(claim(&mut deps, 1, start_at + 1), Ok(1_000));
+        assert_eq!(claim(&mut deps, 1, midnight + 1_800), Ok(1_000));
+
+        // Grant epochs roll over a full day after the cliff ends
+        assert_eq!(claim(&mut deps, 2, start_at + 1), Ok(1_000));
+        let error = extract_error_msg(claim(&mut deps, 2, midnight + 1_800));
+        assert_eq!(error, "Routine claim is already vested!");
+        let error = extract_error_msg(claim(&mut deps, 2, start_at + day - 1));
+        assert_eq!(error, "Routine claim is already vested!");
+        assert_eq!(claim(&mut deps, 2, start_at + day), Ok(1_000));
+        assert_eq!(claim(&mut deps, 2, start_at + 20 * day), Ok(8_000));
+    }
+
+    #[test]
+    fn test_handle_signature_round() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let register_msg = |public_key: Vec<u8>| {
+            HandleMsg::from(RoundMsg {
+                allocator: Some(Allocator {
+                    algorithm: SignatureAlgorithm::Ed25519,
+                    public_key: Binary(public_key),
+                }),
+                ..RoundMsg::default()
+            })
+        };
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            register_msg(vec![7; 32]),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        let config: VestingRoundResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GetConfig {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.merkle_root, None);
+        assert_eq!(config.allocator.unwrap().public_key, Binary(vec![7; 32]));
+
+        let claim = |proof, signature| HandleMsg::Claim {
+            proof,
+            signature,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(10_000),
+            tge: Uint128(0),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(Some(vec![]), None),
+        ));
+        assert_eq!(error, "This round is claimed with allocator signatures!");
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(None, Some(Binary(vec![1; 63]))),
+        ));
+        assert_eq!(error, "Invalid allocation signature length!");
+
+        // The mock api accepts any well formed signature
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(None, Some(Binary(vec![1; 64]))),
+        )
+        .unwrap();
+        let HandleAnswer::Claim { claim_amount, .. } = from_binary(&res.data.unwrap()).unwrap()
+        else {
+            panic!("Unexpected answer")
+        };
+        assert_eq!(claim_amount, Uint128(1_000));
+
+        // Signatures are bound to this contract, unlike merkle leaves
+        assert_ne!(
+            allocation_message_hash(
+                &HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                &HumanAddr("alice".to_string()),
+                stage,
+                0,
+                10_000,
+                0,
+                START_AT,
+                0,
+                10 * day,
+            ),
+            leaf_hash("alice", stage, 10_000, 0, START_AT, 0, 10 * day)
+        );
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            register_msg(vec![7; 31]),
+        ));
+        assert_eq!(error, "Invalid allocator public key!");
+    }
+
+    #[test]
+    fn test_handle_explicit_round() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                total_allocation: Some(Uint128(25_000)),
+                explicit: Some(true),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        let allocation = |address: &str, amount| Allocation {
+            grant: 0,
+            address: HumanAddr(address.to_string()),
+            amount: Uint128(amount),
+            tge: Uint128(0),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+        };
+        let add_msg = |allocations| HandleMsg::AddAllocations {
+            stage: Uint128::from(stage),
+            allocations,
+            padding: None,
+        };
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT),
+            add_msg(vec![allocation("alice", 10_000)]),
+        ));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            add_msg(vec![allocation("alice", 10_000), allocation("bob", 5_000)]),
+        )
+        .unwrap();
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            add_msg(vec![allocation("bob", 5_000)]),
+        ));
+        assert_eq!(error, "An allocation already exists for this grant!");
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            add_msg(vec![allocation("carol", 10_001)]),
+        ));
+        assert_eq!(
+            error,
+            "Allocations exceed the round total allocation of 25000"
+        );
+
+        let config: VestingRoundResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GetConfig {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.total_allocated, Some(Uint128(15_000)));
+
+        // Stored grants are claimed without a proof
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 0, 0, 10 * day),
+        )
+        .unwrap();
+        let HandleAnswer::Claim { claim_amount, .. } = from_binary(&res.data.unwrap()).unwrap()
+        else {
+            panic!("Unexpected answer")
+        };
+        assert_eq!(claim_amount, Uint128(1_000));
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("bob", START_AT + 1),
+            claim_msg(stage, 10_000, 0, 0, 10 * day),
+        ));
+        assert_eq!(error, "Claim does not match the stored allocation!");
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("carol", START_AT + 1),
+            claim_msg(stage, 10_000, 0, 0, 10 * day),
+        ));
+        assert_eq!(error, "No allocation for this address!");
+
+        let query_page = |deps: &Extern<MockStorage, MockApi, TokenQuerier>, page| {
+            let res: AllocationsResponse = from_binary(
+                &query(
+                    deps,
+                    QueryMsg::Allocations {
+                        stage: Uint128::from(stage),
+                        page: Some(page),
+                        page_size: 1,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res
+        };
+        let res = query_page(&deps, 0);
+        assert_eq!(res.total, 2);
+        assert_eq!(res.allocations, vec![allocation("alice", 10_000)]);
+        assert_eq!(
+            query_page(&deps, 1).allocations,
+            vec![allocation("bob", 5_000)]
+        );
+        assert!(query_page(&deps, 2).allocations.is_empty());
+        let error = extract_error_msg(query(
+            &deps,
+            QueryMsg::Allocations {
+                stage: Uint128::from(stage),
+                page: Some(u32::MAX),
+                page_size: 2,
+            },
+        ));
+        assert_eq!(error, "Page out of range!");
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::AddAllocations {
+                stage: Uint128::from(stage),
+                allocations: vec![allocation("erin", 1); MAX_ALLOCATIONS_PER_MSG + 1],
+                padding: None,
+            },
+        ));
+        assert_eq!(error, "At most 100 allocations can be added at once!");
+    }
+
+    #[test]
+    fn test_handle_multiple_grants() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = HumanAddr("alice".to_string());
+        let seed = user_vesting_leaf(&alice, 1, 0, 10_000, 0, START_AT, 0, 10 * day);
+        let advisor = user_vesting_leaf(&alice, 1, 1, 4_000, 0, START_AT, 0, 10 * day);
+        let mut pair = [seed, advisor];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+        let stage = register_round_helper(&mut deps, root);
+
+        let claim = |grant, amount, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant,
+            amount: Uint128(amount),
+            tge: Uint128(0),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        let claim_amount = |res: HandleResponse| {
+            let HandleAnswer::Claim { claim_amount, .. } = from_binary(&res.data.unwrap()).unwrap()
+            else {
+                panic!("Unexpected answer")
+            };
+            claim_amount
+        };
+
+        // Each grant opens its own position
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(None, 10_000, advisor),
+        )
+        .unwrap();
+        assert_eq!(claim_amount(res), Uint128(1_000));
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(Some(1), 4_000, seed),
+        )
+        .unwrap();
+        assert_eq!(claim_amount(res), Uint128(400));
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 2),
+            claim(Some(0), 10_000, advisor),
+        ));
+        assert_eq!(error, "Routine claim is already vested!");
+
+        // The grant index is part of the leaf
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            claim(Some(2), 4_000, seed),
+        ));
+        assert_eq!(error, "Proof verification failed!");
+
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let position: UnlockScheduleResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::PositionUnlockSchedule {
+                    address: alice.clone(),
+                    key: "alice_key".to_string(),
+                    stage: Uint128::from(stage),
+                    grant: Some(1),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(position.linear_amount, Uint128(4_000));
+        assert_eq!(position.total_claimed, Some(Uint128(400)));
+
+        let history: ClaimHistoryResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::ClaimHistory {
+                    address: alice,
+                    key: "alice_key".to_string(),
+                    page: None,
+                    page_size: 10,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let grants: Vec<u64> = history.claims.iter().map(|claim| claim.grant).collect();
+        assert_eq!(grants, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_handle_claim_leaf_mismatch() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = HumanAddr("alice".to_string());
+
+        // A tree listing the same grant twice with different amounts
+        let first = user_vesting_leaf(&alice, 1, 0, 10_000, 0, START_AT, 0, 10 * day);
+        let second = user_vesting_leaf(&alice, 1, 0, 20_000, 0, START_AT, 0, 10 * day);
+        let mut pair = [first, second];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+        let stage = register_round_helper(&mut deps, root);
+
+        let claim = |amount, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128(0),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(10_000, second),
+        )
+        .unwrap();
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 5 * day),
+            claim(20_000, first),
+        ));
+        assert_eq!(
+            error,
+            ContractError::LeafMismatch {
+                stage: Uint128::from(stage),
+                grant: 0,
+            }
+            .to_string()
+        );
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 5 * day),
+            claim(10_000, second),
+        )
+        .unwrap();
+
+        // Positions stored without a leaf are bound to the leaf of their fields
+        let user = deps.api.canonical_address(&alice).unwrap();
+        let mut position = read_user_vesting_stats(&deps.storage, &user, stage, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(position.leaf_hash, Some(Binary(first.to_vec())));
+        position.leaf_hash = None;
+        write_user_vesting_stats(&mut deps.storage, &position, stage).unwrap();
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 7 * day),
+            claim(20_000, first),
+        ));
+        assert_eq!(
+            error,
+            ContractError::LeafMismatch {
+                stage: Uint128::from(stage),
+                grant: 0,
+            }
+            .to_string()
+        );
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 7 * day),
+            claim(10_000, second),
+        )
+        .unwrap();
+        let position = read_user_vesting_stats(&deps.storage, &user, stage, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(position.leaf_hash, Some(Binary(first.to_vec())));
+    }
+
+    #[test]
+    fn test_handle_claim_legacy_position() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = HumanAddr("alice".to_string());
+        let first = user_vesting_leaf(&alice, 1, 0, 10_000, 1_000, START_AT, 0, 10 * day);
+        let second = user_vesting_leaf(&alice, 1, 0, 20_000, 1_000, START_AT, 0, 10 * day);
+        let mut pair = [first, second];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+        let stage = register_round_helper(&mut deps, root);
+
+        // Position as the contract stored it before grants, TGE amounts and leaves were
+        // recorded, with the TGE and the first epoch claimed
+        #[derive(Serialize, Deserialize)]
+        struct LegacyPosition {
+            user: CanonicalAddr,
+            total_amount: Uint128,
+            total_claimed: Uint128,
+            vesting_duration: u64,
+            cliff: u64,
+            tge: Uint128,
+            start_vesting_epoch: u64,
+            next_claim_epoch_index: u64,
+        }
+        let user = deps.api.canonical_address(&alice).unwrap();
+        Bucket::multilevel(
+            &[USER_VESTING_STATS_PREFIX, user.as_slice()],
+            &mut deps.storage,
+        )
+        .save(
+            &stage.to_be_bytes(),
+            &LegacyPosition {
+                user: user.clone(),
+                total_amount: Uint128(9_000),
+                total_claimed: Uint128(1_900),
+                vesting_duration: 10 * day,
+                cliff: 0,
+                tge: Uint128(1_000),
+                start_vesting_epoch: START_AT,
+                next_claim_epoch_index: START_AT / day + 1,
+            },
+        )
+        .unwrap();
+        let position = read_user_vesting_stats(&deps.storage, &user, stage, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(position.leaf_hash, None);
+        assert_eq!(position.tge_amount, Uint128::zero());
+
+        let claim = |amount, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128(1_000),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            claim(20_000, first),
+        ));
+        assert_eq!(
+            error,
+            ContractError::LeafMismatch {
+                stage: Uint128::from(stage),
+                grant: 0,
+            }
+            .to_string()
+        );
+
+        // The next epoch is paid and the position gets its leaf, TGE amount and index entry
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            claim(10_000, second),
+        )
+        .unwrap();
+        let HandleAnswer::Claim {
+            tge_amount,
+            claim_amount,
+            ..
+        } = from_binary(&res.data.unwrap()).unwrap()
+        else {
+            panic!("Unexpected answer")
+        };
+        assert_eq!(tge_amount, Uint128::zero());
+        assert_eq!(claim_amount, Uint128(900));
+
+        let position = read_user_vesting_stats(&deps.storage, &user, stage, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(position.leaf_hash, Some(Binary(first.to_vec())));
+        assert_eq!(position.tge_amount, Uint128(1_000));
+        assert_eq!(position.total_claimed, Uint128(2_800));
+        let (indexed, total) = get_indexed_positions(&deps.storage, stage, None, None).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(indexed[0].user, user);
+    }
+
+    #[test]
+    fn test_handle_round_metadata() {
+        let mut deps = init_helper();
+        let metadata = RoundMetadata {
+            name: Some("Seed".to_string()),
+            description: Some("Seed round, 10% at TGE".to_string()),
+            category: Some(RoundCategory::Seed),
+            uri: Some("ipfs://allocations.json".to_string()),
+            content_hash: Some(hex::encode([1u8; 32])),
+        };
+        let res = handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                merkle_root: Some(hex::encode([0u8; 32])),
+                metadata: Some(metadata.clone()),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        assert!(res.log.contains(&log("name", "Seed")));
+        let seed_stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+        let team_stage = register_round_helper(&mut deps, [0u8; 32]);
+
+        let get_config = |deps: &Extern<MockStorage, MockApi, TokenQuerier>, stage| {
+            let config: VestingRoundResponse = from_binary(
+                &query(
+                    deps,
+                    QueryMsg::GetConfig {
+                        stage: Uint128::from(stage),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            config
+        };
+        assert_eq!(get_config(&deps, seed_stage).metadata, Some(metadata));
+        assert_eq!(get_config(&deps, team_stage).metadata, None);
+
+        let set_metadata = |stage, metadata| HandleMsg::SetRoundMetadata {
+            stage: Uint128::from(stage),
+            metadata,
+            padding: None,
+        };
+        let team = RoundMetadata {
+            name: Some("Team".to_string()),
+            category: Some(RoundCategory::Team),
+            ..RoundMetadata::default()
+        };
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT),
+            set_metadata(team_stage, team.clone()),
+        ));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            set_metadata(team_stage, team.clone()),
+        )
+        .unwrap();
+        assert_eq!(get_config(&deps, team_stage).metadata, Some(team.clone()));
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            set_metadata(
+                team_stage,
+                RoundMetadata {
+                    content_hash: Some("not a hash".to_string()),
+                    ..team.clone()
+                },
+            ),
+        ));
+        assert_eq!(
+            error,
+            "Round content hash must be a hex encoded SHA-256 hash"
+        );
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            set_metadata(
+                team_stage,
+                RoundMetadata {
+                    name: Some("x".repeat(MAX_ROUND_NAME_LEN + 1)),
+                    ..team.clone()
+                },
+            ),
+        ));
+        assert_eq!(error, "Round name must be 1 to 64 bytes long");
+        assert!(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            set_metadata(team_stage + 1, team.clone()),
+        )
+        .is_err());
+
+        let rounds: VestingRoundsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::VestingRounds {
+                    page: None,
+                    page_size: 1,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(rounds.total, 2);
+        assert_eq!(rounds.rounds.len(), 1);
+        assert_eq!(rounds.rounds[0].stage, Uint128::from(team_stage));
+        assert_eq!(rounds.rounds[0].metadata, Some(team));
+    }
+
+    #[test]
+    fn test_query_vesting_rounds_paging() {
+        let mut deps = init_helper();
+        let rounds_query = |deps: &Extern<_, _, _>, page, page_size| {
+            query(deps, QueryMsg::VestingRounds { page, page_size })
+        };
+
+        // No round registered yet
+        let rounds: VestingRoundsResponse =
+            from_binary(&rounds_query(&deps, None, 10).unwrap()).unwrap();
+        assert!(rounds.rounds.is_empty());
+        assert_eq!(rounds.total, 0);
+
+        let first = register_round_helper(&mut deps, [1u8; 32]);
+        let second = register_round_helper(&mut deps, [2u8; 32]);
+        let rounds: VestingRoundsResponse =
+            from_binary(&rounds_query(&deps, Some(1), 1).unwrap()).unwrap();
+        assert_eq!(rounds.total, 2);
+        assert_eq!(rounds.rounds.len(), 1);
+        assert_eq!(rounds.rounds[0].stage, Uint128::from(first));
+        let rounds: VestingRoundsResponse =
+            from_binary(&rounds_query(&deps, None, u32::MAX).unwrap()).unwrap();
+        assert_eq!(rounds.rounds[0].stage, Uint128::from(second));
+
+        let error = extract_error_msg(rounds_query(&deps, Some(u32::MAX), 2));
+        assert_eq!(error, "Page out of range!");
+    }
+
+    #[test]
+    fn test_query_round_stats() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = user_vesting_leaf(
+            &HumanAddr("alice".to_string()),
+            1,
+            0,
+            10_000,
+            1_000,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let bob = user_vesting_leaf(
+            &HumanAddr("bob".to_string()),
+            1,
+            0,
+            5_000,
+            TGE_PRECISION,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let mut pair = [alice, bob];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+        let stage = register_round_helper(&mut deps, root);
+
+        let round_stats = |deps: &Extern<MockStorage, MockApi, TokenQuerier>| {
+            let stats: RoundStatsResponse = from_binary(
+                &query(
+                    deps,
+                    QueryMsg::RoundStats {
+                        stage: Uint128::from(stage),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            stats
+        };
+        assert_eq!(round_stats(&deps).positions, 0);
+        assert_eq!(round_stats(&deps).first_claim_at, None);
+
+        let claim = |amount, tge, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128(tge),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(10_000, 1_000, bob),
+        )
+        .unwrap();
+        // The whole grant of bob is paid at TGE
+        handle(
+            &mut deps,
+            env_at("bob", START_AT + day),
+            claim(5_000, TGE_PRECISION, alice),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 10 * day),
+            claim(10_000, 1_000, bob),
+        )
+        .unwrap();
+
+        assert_eq!(
+            round_stats(&deps),
+            RoundStatsResponse {
+                stage: Uint128::from(stage),
+                positions: 2,
+                fully_vested_positions: 2,
+                tge_paid: Uint128(6_000),
+                linear_paid: Uint128(9_000),
+                claim_count: 3,
+                first_claim_at: Some(START_AT + 1),
+                last_claim_at: Some(START_AT + 10 * day),
+                imported_amount: Uint128::zero(),
+                imported_claimed: Uint128::zero(),
+                fees_paid: Uint128::zero(),
+            }
+        );
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 11 * day),
+            claim(10_000, 1_000, bob),
+        ));
+        assert_eq!(error, "Exceeds maximum claim amount!");
+        assert_eq!(round_stats(&deps).claim_count, 3);
+
+        assert!(query(
+            &deps,
+            QueryMsg::RoundStats {
+                stage: Uint128::from(stage + 1),
+            },
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_query_reconcile() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = |user: &str, amount| {
+            user_vesting_leaf(
+                &HumanAddr(user.to_string()),
+                1,
+                0,
+                amount,
+                1_000,
+                START_AT,
+                0,
+                10 * day,
+            )
+        };
+        let (alice, bob) = (leaf("alice", 10_000), leaf("bob", 5_000));
+        let mut pair = [alice, bob];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+        let stage = register_round_helper(&mut deps, root);
+
+        let claim = |amount, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128(1_000),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", START_AT + 1), claim(10_000, bob)).unwrap();
+        handle(
+            &mut deps,
+            env_at("bob", START_AT + day),
+            claim(5_000, alice),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 3 * day),
+            claim(10_000, bob),
+        )
+        .unwrap();
+
+        for user in &["admin", "alice"] {
+            handle(
+                &mut deps,
+                mock_env(*user, &[]),
+                HandleMsg::SetViewingKey {
+                    key: format!("{}_key", user),
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+        let reconcile = |deps: &Extern<MockStorage, MockApi, TokenQuerier>,
+                         user: &str,
+                         start_after,
+                         claimed_before| {
+            query(
+                deps,
+                QueryMsg::Reconcile {
+                    address: HumanAddr(user.to_string()),
+                    key: format!("{}_key", user),
+                    stage: Uint128::from(stage),
+                    start_after,
+                    limit: Some(1),
+                    claimed_before,
+                },
+            )
+            .and_then(|res| from_binary::<ReconcileResponse>(&res))
+        };
+
+        let error = extract_error_msg(reconcile(&deps, "alice", None, None));
+        assert_eq!(
+            error,
+            "This is an admin query. Admin queries can only be run from admin address"
+        );
+
+        let first = reconcile(&deps, "admin", None, None).unwrap();
+        assert_eq!(first.total_positions, 2);
+        assert_eq!(first.last, Some(0));
+        assert_eq!(first.discrepancies, vec![]);
+        let second = reconcile(&deps, "admin", first.last, Some(first.claimed_through)).unwrap();
+        assert_eq!(second.last, Some(1));
+        assert_eq!(second.discrepancies, vec![]);
+        let config: VestingRoundResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GetConfig {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(second.claimed_through, config.total_claimed);
+
+        // Books that don't balance are reported
+        let user = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        let mut position = read_user_vesting_stats(&deps.storage, &user, stage, 0)
+            .unwrap()
+            .unwrap();
+        let linear_claimed = position.linear_claimed();
+        position.total_claimed = Uint128(position.total_claimed.u128() + 50);
+        write_user_vesting_stats(&mut deps.storage, &position, stage).unwrap();
+
+        let first = reconcile(&deps, "admin", None, None).unwrap();
+        assert_eq!(
+            first.discrepancies,
+            vec![Discrepancy {
+                kind: DiscrepancyKind::EpochMismatch,
+                address: Some(HumanAddr("alice".to_string())),
+                grant: Some(0),
+                expected: Uint128(linear_claimed),
+                actual: Uint128(linear_claimed + 50),
+            }]
+        );
+        let second = reconcile(&deps, "admin", first.last, Some(first.claimed_through)).unwrap();
+        assert_eq!(
+            second.discrepancies,
+            vec![Discrepancy {
+                kind: DiscrepancyKind::RoundTotalMismatch,
+                address: None,
+                grant: None,
+                expected: config.total_claimed,
+                actual: Uint128(config.total_claimed.u128() + 50),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_query_export_positions() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = user_vesting_leaf(
+            &HumanAddr("alice".to_string()),
+            1,
+            0,
+            10_000,
+            1_000,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let bob = user_vesting_leaf(
+            &HumanAddr("bob".to_string()),
+            1,
+            0,
+            5_000,
+            TGE_PRECISION,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let mut pair = [alice, bob];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+        let stage = register_round_helper(&mut deps, root);
+
+        let claim = |amount, tge, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128(tge),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(10_000, 1_000, bob),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("bob", START_AT + 1),
+            claim(5_000, TGE_PRECISION, alice),
+        )
+        .unwrap();
+
+        for user in &["admin", "alice"] {
+            handle(
+                &mut deps,
+                mock_env(*user, &[]),
+                HandleMsg::SetViewingKey {
+                    key: format!("{}_key", user),
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+        let export =
+            |deps: &Extern<MockStorage, MockApi, TokenQuerier>, user: &str, start_after| {
+                query(
+                    deps,
+                    QueryMsg::ExportPositions {
+                        address: HumanAddr(user.to_string()),
+                        key: format!("{}_key", user),
+                        stage: Uint128::from(stage),
+                        start_after,
+                        limit: Some(1),
+                    },
+                )
+                .and_then(|res| from_binary::<ExportPositionsResponse>(&res))
+            };
+
+        let error = extract_error_msg(export(&deps, "alice", None));
+        assert_eq!(
+            error,
+            "This is an admin query. Admin queries can only be run from admin address"
+        );
+
+        let first = export(&deps, "admin", None).unwrap();
+        assert_eq!(first.total, 2);
+        assert_eq!(
+            first.positions,
+            vec![PositionRecord {
+                address: HumanAddr("alice".to_string()),
+                grant: 0,
+                amount: Uint128(10_000),
+                tge: Uint128(1_000),
+                start_at: START_AT,
+                cliff: 0,
+                duration: 10 * day,
+                leaf_hash: Some(Binary(alice.to_vec())),
+                total_claimed: Uint128(1_900),
+                tge_amount: Uint128(1_000),
+                next_claim_epoch_index: START_AT / day + 1,
+                status: PositionStatus::Vesting,
+            }]
+        );
+
+        let second = export(&deps, "admin", first.last).unwrap();
+        assert_eq!(second.positions.len(), 1);
+        assert_eq!(second.positions[0].address, HumanAddr("bob".to_string()));
+        assert_eq!(second.positions[0].amount, Uint128(5_000));
+        assert_eq!(second.positions[0].status, PositionStatus::FullyVested);
+        assert!(export(&deps, "admin", second.last)
+            .unwrap()
+            .positions
+            .is_empty());
+
+        // Limits past the page size are capped rather than refused
+        let all: ExportPositionsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::ExportPositions {
+                    address: HumanAddr("admin".to_string()),
+                    key: "admin_key".to_string(),
+                    stage: Uint128::from(stage),
+                    start_after: None,
+                    limit: Some(u32::MAX),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(all.positions.len(), 2);
+    }
+
+    #[test]
+    fn test_handle_import_positions() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = user_vesting_leaf(
+            &HumanAddr("alice".to_string()),
+            1,
+            0,
+            10_000,
+            1_000,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let bob = user_vesting_leaf(
+            &HumanAddr("bob".to_string()),
+            1,
+            0,
+            5_000,
+            TGE_PRECISION,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let mut pair = [alice, bob];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                total_allocation: Some(Uint128(15_000)),
+                import_positions: Some(true),
+                merkle_root: Some(hex::encode(root)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let stage = 1u128;
+
+        // Positions as exported by the previous contract, one epoch into alice's schedule
+        let record = |address: &str, amount, tge, claimed, tge_amount, next_claim_epoch_index| {
+            PositionRecord {
+                address: HumanAddr(address.to_string()),
+                grant: 0,
+                amount: Uint128(amount),
+                tge: Uint128(tge),
+                start_at: START_AT,
+                cliff: 0,
+                duration: 10 * day,
+                leaf_hash: None,
+                total_claimed: Uint128(claimed),
+                tge_amount: Uint128(tge_amount),
+                next_claim_epoch_index,
+                status: PositionStatus::Vesting,
+            }
+        };
+        let import = |position, sibling: [u8; 32]| PositionImport {
+            position,
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+        };
+        let import_msg = |positions| HandleMsg::ImportPositions {
+            stage: Uint128::from(stage),
+            positions,
+            multiproof: None,
+            padding: None,
+        };
+        let alice_claim = HandleMsg::Claim {
+            proof: Some(vec![hex::encode(bob)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(10_000),
+            tge: Uint128(1_000),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        let alice_record = record("alice", 10_000, 1_000, 1_900, 1_000, START_AT / day + 1);
+        let bob_record = record("bob", 5_000, TGE_PRECISION, 5_000, 5_000, START_AT / day);
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            import_msg(vec![import(alice_record.clone(), bob)]),
+        ));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            alice_claim.clone(),
+        ));
+        assert_eq!(error, "Vesting round is not activated yet!");
+
+        // Claimed amounts must follow the schedule
+        let mut overclaimed = alice_record.clone();
+        overclaimed.total_claimed = Uint128(2_000);
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(vec![import(overclaimed, bob)]),
+        ));
+        assert_eq!(error, "Invalid position for grant 0 of alice");
+
+        let mut unknown = alice_record.clone();
+        unknown.amount = Uint128(20_000);
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(vec![import(unknown, bob)]),
+        ));
+        assert_eq!(error, "Proof verification failed!");
+
+        let res = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(vec![
+                import(alice_record.clone(), bob),
+                import(bob_record, alice),
+            ]),
+        )
+        .unwrap();
+        assert!(res.log.contains(&log("event", "import_positions")));
+        assert!(res.log.contains(&log("count", 2)));
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(vec![import(alice_record.clone(), bob)]),
+        ));
+        assert_eq!(error, "A position already exists for grant 0 of alice");
+
+        let stats: RoundStatsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::RoundStats {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(stats.positions, 2);
+        assert_eq!(stats.fully_vested_positions, 1);
+        assert_eq!(stats.imported_amount, Uint128(15_000));
+        assert_eq!(stats.imported_claimed, Uint128(6_900));
+        assert_eq!(stats.claim_count, 0);
+
+        // What the previous contract paid out is not owed anymore
+        let status: RoundFundingStatusResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::RoundFundingStatus {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(status.total_claimed, Uint128::zero());
+        assert_eq!(status.outstanding, Some(Uint128(8_100)));
+
+        let activate = HandleMsg::ActivateRound {
+            stage: Uint128::from(stage),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("admin", &[]), activate.clone()).unwrap();
+        let error = extract_error_msg(handle(&mut deps, mock_env("admin", &[]), activate));
+        assert_eq!(error, "Vesting round is already activated!");
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(vec![]),
+        ));
+        assert_eq!(
+            error,
+            "Positions can only be imported before the round is activated!"
+        );
+
+        // Pausing the round turns claims down until it is resumed
+        let set_paused = |is_paused| HandleMsg::SetRoundPaused {
+            stage: Uint128::from(stage),
+            is_paused,
+            padding: None,
+        };
+        let error = extract_error_msg(handle(&mut deps, mock_env("alice", &[]), set_paused(true)));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+        let res = handle(&mut deps, mock_env("admin", &[]), set_paused(true)).unwrap();
+        assert!(res.log.contains(&log("is_paused", true)));
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            alice_claim.clone(),
+        ));
+        assert_eq!(error, "Vesting round is paused!");
+        handle(&mut deps, mock_env("admin", &[]), set_paused(false)).unwrap();
+
+        // The imported position carries on from its next epoch
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            alice_claim.clone(),
+        )
+        .unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Claim {
+                tge_amount,
+                claim_amount,
+                ..
+            } => {
+                assert_eq!(tge_amount, Uint128::zero());
+                assert_eq!(claim_amount, Uint128(900));
+            }
+            answer => panic!("Unexpected answer: {:?}", answer),
+        }
+
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetViewingKey {
+                key: "admin_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let reconcile: ReconcileResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::Reconcile {
+                    address: HumanAddr("admin".to_string()),
+                    key: "admin_key".to_string(),
+                    stage: Uint128::from(stage),
+                    start_after: None,
+                    limit: None,
+                    claimed_before: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(reconcile.discrepancies, vec![]);
+    }
+
+    #[test]
+    fn test_handle_import_positions_multiproof() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = |address: &str, amount, tge| {
+            user_vesting_leaf(
+                &HumanAddr(address.to_string()),
+                1,
+                0,
+                amount,
+                tge,
+                START_AT,
+                0,
+                10 * day,
+            )
+        };
+        let hash_pair = |a: [u8; 32], b: [u8; 32]| {
+            let mut pair = [a, b];
+            pair.sort_unstable();
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+            hash
+        };
+        let alice = leaf("alice", 10_000, 1_000);
+        let bob = leaf("bob", 5_000, TGE_PRECISION);
+        let carol = leaf("carol", 8_000, 0);
+        let root = hash_pair(hash_pair(alice, bob), carol);
+
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                import_positions: Some(true),
+                merkle_root: Some(hex::encode(root)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+
+        let record = |address: &str, amount, tge, claimed, tge_amount, next_claim_epoch_index| {
+            PositionRecord {
+                address: HumanAddr(address.to_string()),
+                grant: 0,
+                amount: Uint128(amount),
+                tge: Uint128(tge),
+                start_at: START_AT,
+                cliff: 0,
+                duration: 10 * day,
+                leaf_hash: None,
+                total_claimed: Uint128(claimed),
+                tge_amount: Uint128(tge_amount),
+                next_claim_epoch_index,
+                status: PositionStatus::Vesting,
+            }
+        };
+        let import = |position| PositionImport {
+            position,
+            proof: None,
+            signature: None,
+        };
+        let alice_record = record("alice", 10_000, 1_000, 1_900, 1_000, START_AT / day + 1);
+        let bob_record = record("bob", 5_000, TGE_PRECISION, 5_000, 5_000, START_AT / day);
+        // Alice and bob hash to the same parent, only carol's leaf is needed on the way up
+        let multiproof = MultiProof {
+            proof: vec![hex::encode(carol)],
+            proof_flags: vec![true, false],
+        };
+        let import_msg = |positions, multiproof| HandleMsg::ImportPositions {
+            stage: Uint128(1),
+            positions,
+            multiproof,
+            padding: None,
+        };
+
+        let mut unknown = bob_record.clone();
+        unknown.address = HumanAddr("dave".to_string());
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(
+                vec![import(alice_record.clone()), import(unknown)],
+                Some(multiproof.clone()),
+            ),
+        ));
+        assert_eq!(error, "Proof verification failed!");
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(
+                vec![
+                    import(alice_record.clone()),
+                    PositionImport {
+                        proof: Some(vec![hex::encode(alice)]),
+                        ..import(bob_record.clone())
+                    },
+                ],
+                Some(multiproof.clone()),
+            ),
+        ));
+        assert_eq!(
+            error,
+            "Positions proven by a multiproof can't carry their own proof!"
+        );
+
+        let res = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            import_msg(
+                vec![import(alice_record), import(bob_record)],
+                Some(multiproof.clone()),
+            ),
+        )
+        .unwrap();
+        assert!(res.log.contains(&log("count", 2)));
+        let bob_stats = read_user_vesting_stats(
+            &deps.storage,
+            &deps
+                .api
+                .canonical_address(&HumanAddr("bob".to_string()))
+                .unwrap(),
+            1,
+            0,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(bob_stats.leaf_hash, Some(Binary(bob.to_vec())));
+
+        // Signature and explicit rounds have no tree to prove against
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                import_positions: Some(true),
+                explicit: Some(true),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::ImportPositions {
+                stage: Uint128(2),
+                positions: vec![],
+                multiproof: Some(multiproof),
+                padding: None,
+            },
+        ));
+        assert_eq!(error, "Multiproofs can only be used in merkle rounds!");
+    }
+
+    #[test]
+    fn test_handle_claim_private_amounts() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SetPrivateAmounts {
+                enabled: true,
+                padding: None,
+            },
+        ));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetPrivateAmounts {
+                enabled: true,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+        assert!(res
+            .log
+            .iter()
+            .all(|log| log.key != "tge_amount" && log.key != "claim_amount"));
+
+        let data = res.data.unwrap();
+        assert_eq!(data.len() % RESPONSE_BLOCK_SIZE, 0);
+        assert_eq!(
+            from_binary::<HandleAnswer>(&data).unwrap(),
+            HandleAnswer::Claim {
+                status: ResponseStatus::Success,
+                tge_amount: Uint128(1_000),
+                claim_amount: Uint128(900),
+                fee_amount: None,
+                reward_amount: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_private_amounts_deposit() {
+        let mut deps = init_helper();
+        let leaf = leaf_hash("alice", 1, 10_000, 0, START_AT, 0, ONE_DAY_IN_SECONDS);
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetPrivateAmounts {
+                enabled: true,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let res = handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                custody: Some(true),
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let data = res.data.unwrap();
+        assert_eq!(data.len() % RESPONSE_BLOCK_SIZE, 0);
+        let stage = match from_binary(&data).unwrap() {
+            HandleAnswer::RegisterNewVestingRound { status, stage } => {
+                assert_eq!(status, ResponseStatus::Success);
+                stage
+            }
+            answer => panic!("Unexpected answer: {:?}", answer),
+        };
+
+        let res = handle(
+            &mut deps,
+            mock_env("token", &[]),
+            HandleMsg::Receive {
+                sender: HumanAddr("treasury".to_string()),
+                from: HumanAddr("treasury".to_string()),
+                amount: Uint128(10_000),
+                memo: None,
+                msg: Some(to_binary(&ReceiveMsg::FundRound { stage }).unwrap()),
+            },
+        )
+        .unwrap();
+        assert!(res.log.contains(&log("event", "fund_round")));
+        assert!(res
+            .log
+            .iter()
+            .all(|log| log.key != "funded_amount" && log.key != "total_funded"));
+
+        let data = res.data.unwrap();
+        assert_eq!(data.len() % RESPONSE_BLOCK_SIZE, 0);
+        assert_eq!(
+            from_binary::<HandleAnswer>(&data).unwrap(),
+            HandleAnswer::Receive {
+                status: ResponseStatus::Success,
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_claim_fee() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let register_msg = |rate_bps| {
+            HandleMsg::from(RoundMsg {
+                merkle_root: Some(hex::encode(leaf)),
+                fee: Some(RoundFee {
+                    rate_bps,
+                    recipient: HumanAddr("treasury".to_string()),
+                }),
+                ..RoundMsg::default()
+            })
+        };
+
+        handle(&mut deps, env_at("admin", START_AT), register_msg(250)).unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            register_msg(MAX_CLAIM_FEE_BPS + 1),
+        ));
+        assert_eq!(error, "Claim fee can be at most 1000 basis points!");
+
+        let config: VestingRoundResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GetConfig {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.fee,
+            Some(RoundFee {
+                rate_bps: 250,
+                recipient: HumanAddr("treasury".to_string()),
+            })
+        );
+
+        // 2.5% of each payout, rounded down, goes to the treasury
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+        let transfer = |recipient: &str, amount, memo: &str| {
+            snip20::transfer_from_msg(
+                HumanAddr("distribution".to_string()),
+                HumanAddr(recipient.to_string()),
+                Uint128(amount),
+                Some(memo.to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                transfer("alice", 975, "Pay TGE"),
+                transfer("treasury", 25, "Claim fee"),
+                transfer("alice", 878, "Pay Claim amount"),
+                transfer("treasury", 22, "Claim fee"),
+            ]
+        );
+        assert!(res.log.contains(&log("fee_amount", 47)));
+
+        let HandleAnswer::Claim {
+            tge_amount,
+            claim_amount,
+            fee_amount,
+            ..
+        } = from_binary(&res.data.unwrap()).unwrap()
+        else {
+            panic!("Unexpected answer")
+        };
+        assert_eq!(tge_amount, Uint128(1_000));
+        assert_eq!(claim_amount, Uint128(900));
+        assert_eq!(fee_amount, Some(Uint128(47)));
+
+        let stats: RoundStatsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::RoundStats {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(stats.tge_paid, Uint128(1_000));
+        assert_eq!(stats.linear_paid, Uint128(900));
+        assert_eq!(stats.fees_paid, Uint128(47));
+    }
+
+    #[test]
+    fn test_handle_claim_fee_escrow() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let mut env = env_at("admin", START_AT);
+        env.message.sent_funds = vec![Coin::new(990, "uscrt")];
+        handle(
+            &mut deps,
+            env,
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                token_address: None,
+                token_code_hash: None,
+                denom: Some("uscrt".to_string()),
+                merkle_root: Some(hex::encode(leaf)),
+                fee: Some(RoundFee {
+                    rate_bps: 250,
+                    recipient: HumanAddr("treasury".to_string()),
+                }),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        // The escrow covers the TGE less its fee and the fee alone, but not both
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(
+            error,
+            "Insufficient Funds: Contract balance: 990 does not cover the required amount: 1000"
+        );
+    }
+
+    #[test]
+    fn test_handle_claim_msg() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        let claim = |msg| HandleMsg::Claim {
+            proof: None,
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(10_000),
+            tge: Uint128(1_000),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg,
+            padding: None,
+        };
+        let set_claim_msg = |msg| HandleMsg::SetClaimMsg {
+            stage: Uint128::from(stage),
+            grant: None,
+            msg,
+            padding: None,
+        };
+        let send = |amount, msg: &[u8], memo: &str| {
+            snip20::send_from_msg(
+                HumanAddr("distribution".to_string()),
+                HumanAddr("alice".to_string()),
+                Uint128(amount),
+                Some(Binary::from(msg)),
+                Some(memo.to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()
+        };
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            set_claim_msg(Some(Binary::from(b"stake"))),
+        ));
+        assert_eq!(error, "No vesting position for this address");
+
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim(Some(Binary::from(b"stake"))),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                send(1_000, b"stake", "Pay TGE"),
+                send(900, b"stake", "Pay Claim amount"),
+            ]
+        );
+
+        // Later claims without a message use the default of the position
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            set_claim_msg(Some(Binary::from(b"vault"))),
+        )
+        .unwrap();
+        let res = handle(&mut deps, env_at("alice", START_AT + day), claim(None)).unwrap();
+        assert_eq!(res.messages, vec![send(900, b"vault", "Pay Claim amount")]);
+
+        handle(&mut deps, mock_env("alice", &[]), set_claim_msg(None)).unwrap();
+        let res = handle(&mut deps, env_at("alice", START_AT + 2 * day), claim(None)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![snip20::transfer_from_msg(
+                HumanAddr("distribution".to_string()),
+                HumanAddr("alice".to_string()),
+                Uint128(900),
+                Some("Pay Claim amount".to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_handle_staking_round() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = user_vesting_leaf(
+            &HumanAddr("alice".to_string()),
+            1,
+            0,
+            10_000,
+            0,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let bob = user_vesting_leaf(
+            &HumanAddr("bob".to_string()),
+            1,
+            0,
+            30_000,
+            0,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let mut pair = [alice, bob];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+
+        let register_msg = || {
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                custody: Some(true),
+                merkle_root: Some(hex::encode(root)),
+                staking: Some(RoundStaking {
+                    address: HumanAddr(STAKING_ADDR.to_string()),
+                    code_hash: "staking_hash".to_string(),
+                }),
+                ..RoundMsg::default()
+            })
+        };
+        let res = handle(&mut deps, env_at("admin", START_AT), register_msg()).unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+        assert!(res.messages.iter().any(|message| matches!(
+            message,
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. })
+                if contract_addr.as_str() == STAKING_ADDR
+        )));
+
+        // Deposits are staked as they come in
+        let res = handle(
+            &mut deps,
+            mock_env("token", &[]),
+            HandleMsg::Receive {
+                sender: HumanAddr("treasury".to_string()),
+                from: HumanAddr("treasury".to_string()),
+                amount: Uint128(40_000),
+                memo: None,
+                msg: Some(
+                    to_binary(&ReceiveMsg::FundRound {
+                        stage: Uint128::from(stage),
+                    })
+                    .unwrap(),
+                ),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![snip20::send_msg(
+                HumanAddr(STAKING_ADDR.to_string()),
+                Uint128(40_000),
+                Some(to_binary(&StakingReceiveMsg::Stake {}).unwrap()),
+                None,
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()]
+        );
+        deps.querier.execute_staking(&res.messages);
+        assert_eq!(deps.querier.staking.staked, 40_000);
+
+        let claim = |amount, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128::zero(),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        let unstake = |amount| {
+            StakingHandleMsg::Unstake {
+                amount: Uint128(amount),
+            }
+            .to_cosmos_msg(
+                "staking_hash".to_string(),
+                HumanAddr(STAKING_ADDR.to_string()),
+                None,
+            )
+            .unwrap()
+        };
+        let transfer = |recipient: &str, amount, memo: &str| {
+            snip20::transfer_msg(
+                HumanAddr(recipient.to_string()),
+                Uint128(amount),
+                Some(memo.to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()
+        };
+
+        let res = handle(&mut deps, env_at("alice", START_AT + 1), claim(10_000, bob)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![unstake(1_000), transfer("alice", 1_000, "Pay Claim amount")]
+        );
+        deps.querier.execute_staking(&res.messages);
+
+        // 390 over the 39_000 staked tokens, bob's position opens after they were earned
+        deps.querier.staking.rewards = 390;
+        let res = handle(&mut deps, env_at("bob", START_AT + 1), claim(30_000, alice)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                StakingHandleMsg::ClaimRewards {}
+                    .to_cosmos_msg(
+                        "staking_hash".to_string(),
+                        HumanAddr(STAKING_ADDR.to_string()),
+                        None,
+                    )
+                    .unwrap(),
+                unstake(3_000),
+                transfer("bob", 3_000, "Pay Claim amount"),
+            ]
+        );
+        let HandleAnswer::Claim { reward_amount, .. } = from_binary(&res.data.unwrap()).unwrap()
+        else {
+            panic!("Unexpected answer")
+        };
+        assert_eq!(reward_amount, Some(Uint128::zero()));
+        deps.querier.execute_staking(&res.messages);
+        assert_eq!(deps.querier.staking.rewards, 0);
+
+        // Alice earned on the 9_000 tokens she had not vested yet
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            claim(10_000, bob),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                unstake(1_000),
+                transfer("alice", 1_000, "Pay Claim amount"),
+                transfer("alice", 90, "Pay staking rewards"),
+            ]
+        );
+        let HandleAnswer::Claim { reward_amount, .. } = from_binary(&res.data.unwrap()).unwrap()
+        else {
+            panic!("Unexpected answer")
+        };
+        assert_eq!(reward_amount, Some(Uint128(90)));
+        deps.querier.execute_staking(&res.messages);
+
+        let status: StakingStatusResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::StakingStatus {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            status,
+            StakingStatusResponse {
+                stage: Uint128::from(stage),
+                staking: RoundStaking {
+                    address: HumanAddr(STAKING_ADDR.to_string()),
+                    code_hash: "staking_hash".to_string(),
+                },
+                total_staked: Uint128(35_000),
+                reward_per_token: Uint128(10_000_000_000),
+                rewards_harvested: Uint128(390),
+                rewards_paid: Uint128(90),
+                unassigned_rewards: Uint128(300),
+                rewards_swept: Uint128::zero(),
+            }
+        );
+        assert_eq!(status.total_staked.u128(), deps.querier.staking.staked);
+
+        let error = extract_error_msg(handle(&mut deps, env_at("admin", START_AT), register_msg()));
+        assert_eq!(error, "Staking contract is already used by round 1");
+    }
+
+    #[test]
+    fn test_handle_staking_unassigned_rewards() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let alice = user_vesting_leaf(
+            &HumanAddr("alice".to_string()),
+            1,
+            0,
+            10_000,
+            0,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let bob = user_vesting_leaf(
+            &HumanAddr("bob".to_string()),
+            1,
+            0,
+            30_000,
+            0,
+            START_AT,
+            0,
+            10 * day,
+        );
+        let mut pair = [alice, bob];
+        pair.sort_unstable();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(sha2::Sha256::digest(&pair.concat()).as_slice());
+
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                custody: Some(true),
+                merkle_root: Some(hex::encode(root)),
+                staking: Some(RoundStaking {
+                    address: HumanAddr(STAKING_ADDR.to_string()),
+                    code_hash: "staking_hash".to_string(),
+                }),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+        let res = handle(
+            &mut deps,
+            mock_env("token", &[]),
+            HandleMsg::Receive {
+                sender: HumanAddr("treasury".to_string()),
+                from: HumanAddr("treasury".to_string()),
+                amount: Uint128(40_000),
+                memo: None,
+                msg: Some(
+                    to_binary(&ReceiveMsg::FundRound {
+                        stage: Uint128::from(stage),
+                    })
+                    .unwrap(),
+                ),
+            },
+        )
+        .unwrap();
+        deps.querier.execute_staking(&res.messages);
+
+        let claim = |amount, sibling: [u8; 32]| HandleMsg::Claim {
+            proof: Some(vec![hex::encode(sibling)]),
+            signature: None,
+            stage: Uint128::from(stage),
+            grant: None,
+            amount: Uint128(amount),
+            tge: Uint128::zero(),
+            start_at: START_AT,
+            cliff: 0,
+            duration: 10 * day,
+            msg: None,
+            padding: None,
+        };
+        let reward_amount = |res: &HandleResponse| {
+            let HandleAnswer::Claim { reward_amount, .. } =
+                from_binary(res.data.as_ref().unwrap()).unwrap()
+            else {
+                panic!("Unexpected answer")
+            };
+            reward_amount.unwrap().u128()
+        };
+        let sweep_msg = || HandleMsg::SweepStakingRewards {
+            stage: Uint128::from(stage),
+            recipient: HumanAddr("treasury".to_string()),
+            padding: None,
+        };
+
+        // Harvested before any position opens, none of it is owed to alice
+        deps.querier.staking.rewards = 400;
+        let res = handle(&mut deps, env_at("alice", START_AT + 1), claim(10_000, bob)).unwrap();
+        assert_eq!(reward_amount(&res), 0);
+        deps.querier.execute_staking(&res.messages);
+
+        // 90 of these are alice's, bob's 30_000 tokens were not claimed yet
+        deps.querier.staking.rewards = 390;
+        let res = handle(&mut deps, env_at("bob", START_AT + 1), claim(30_000, alice)).unwrap();
+        assert_eq!(reward_amount(&res), 0);
+        deps.querier.execute_staking(&res.messages);
+
+        deps.querier.staking.rewards = 360;
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            claim(10_000, bob),
+        )
+        .unwrap();
+        assert_eq!(reward_amount(&res), 180);
+        deps.querier.execute_staking(&res.messages);
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + day),
+            sweep_msg(),
+        ));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+        let res = handle(&mut deps, env_at("admin", START_AT + day), sweep_msg()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("treasury".to_string()),
+                Uint128(700),
+                Some("Pay staking rewards".to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()]
+        );
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT + day),
+            sweep_msg(),
+        ));
+        assert_eq!(error, "No unassigned staking rewards to sweep");
+
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 10 * day),
+            claim(10_000, bob),
+        )
+        .unwrap();
+        assert_eq!(reward_amount(&res), 0);
+        deps.querier.execute_staking(&res.messages);
+        let res = handle(
+            &mut deps,
+            env_at("bob", START_AT + 10 * day),
+            claim(30_000, alice),
+        )
+        .unwrap();
+        assert_eq!(reward_amount(&res), 270);
+        deps.querier.execute_staking(&res.messages);
+
+        // Every harvested reward went to a position or to the sweep
+        let status: StakingStatusResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::StakingStatus {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(status.total_staked, Uint128::zero());
+        assert_eq!(deps.querier.staking.staked, 0);
+        assert_eq!(status.rewards_harvested, Uint128(1_150));
+        assert_eq!(status.rewards_paid, Uint128(450));
+        assert_eq!(status.rewards_swept, Uint128(700));
+        assert_eq!(status.unassigned_rewards, Uint128::zero());
+    }
+
+    #[test]
+    fn test_handle_contract_status() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        let set_status = |level| HandleMsg::SetContractStatus {
+            level,
+            padding: None,
+        };
+        let query_status = |deps: &Extern<MockStorage, MockApi, TokenQuerier>| {
+            from_binary::<ContractStatusResponse>(
+                &query(deps, QueryMsg::ContractStatus {}).unwrap(),
+            )
+            .unwrap()
+            .status
+        };
+
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            set_status(ContractStatusLevel::StopRegistrations),
+        )
+        .unwrap();
+        assert_eq!(query_status(&deps), ContractStatusLevel::StopRegistrations);
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            }),
+        ));
+        assert_eq!(error, "Registration of new vesting rounds is stopped");
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            set_status(ContractStatusLevel::StopClaims),
+        )
+        .unwrap();
+        assert_eq!(query_status(&deps), ContractStatusLevel::StopClaims);
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 2 * day),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(error, "Claims are stopped");
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetPrivateAmounts {
+                enabled: true,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            set_status(ContractStatusLevel::StopAll),
+        )
+        .unwrap();
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetPrivateAmounts {
+                enabled: false,
+                padding: None,
+            },
+        ));
+        assert_eq!(
+            error,
+            "This contract is stopped and this action is not allowed"
+        );
+
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            set_status(ContractStatusLevel::NormalRun),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 2 * day),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_handle_native_round() {
+        let mut deps = init_helper_with_balance(&[Coin::new(1_000, "uscrt")]);
+
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let register_msg = HandleMsg::from(RoundMsg {
+            distribution: None,
+            token_address: None,
+            token_code_hash: None,
+            denom: Some("uscrt".to_string()),
+            merkle_root: Some(hex::encode(leaf)),
+            ..RoundMsg::default()
+        });
+
+        let mut env = env_at("admin", START_AT);
+        env.message.sent_funds = vec![Coin::new(1_000, "uscrt")];
+        let res = handle(&mut deps, env, register_msg).unwrap();
+        assert!(res.messages.is_empty());
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        // Round escrow only covers the TGE and the first daily unlock
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(
+            error,
+            "Insufficient Funds: Contract balance: 0 does not cover the required amount: 900"
+        );
+
+        let fund_msg = HandleMsg::FundRound {
+            stage: Uint128::from(stage),
+            padding: None,
+        };
+        let mut env = env_at("funder", START_AT);
+        env.message.sent_funds = vec![Coin::new(100, "uatom")];
+        let error = extract_error_msg(handle(&mut deps, env.clone(), fund_msg.clone()));
+        assert_eq!(
+            error,
+            "Unexpected denom uatom, this round only accepts uscrt"
+        );
+
+        env.message.sent_funds = vec![Coin::new(9_000, "uscrt")];
+        handle(&mut deps, env, fund_msg).unwrap();
+
+        // The round is funded but the contract bank balance is not
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(
+            error,
+            "Insufficient Funds: Contract balance: 1000 does not cover the required amount: 1900"
+        );
+
+        deps.querier
+            .base
+            .update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(10_000, "uscrt")]);
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                to_address: HumanAddr("alice".to_string()),
+                amount: vec![Coin::new(1_000, "uscrt")],
+            })
+        );
+
+        let round: VestingRoundResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GetConfig {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(round.denom, Some("uscrt".to_string()));
+        assert_eq!(round.token_address, None);
+        assert_eq!(round.total_funded, Uint128(10_000));
+        assert_eq!(round.total_claimed, Uint128(1_900));
+    }
+
+    #[test]
+    fn test_handle_custody_round() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 0, START_AT, 0, 10 * day);
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                custody: Some(true),
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+
+        let receive_msg = HandleMsg::Receive {
+            sender: HumanAddr("treasury".to_string()),
+            from: HumanAddr("treasury".to_string()),
+            amount: Uint128(5_000),
+            memo: None,
+            msg: Some(
+                to_binary(&ReceiveMsg::FundRound {
+                    stage: Uint128::from(stage),
+                })
+                .unwrap(),
+            ),
+        };
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("other_token", &[]),
+            receive_msg.clone(),
+        ));
+        assert_eq!(
+            error,
+            "This vesting round does not accept deposits of this token!"
+        );
+        handle(&mut deps, mock_env("token", &[]), receive_msg).unwrap();
+
+        let res = handle(
+            &mut deps,
+            env_at("alice", START_AT + 3 * day),
+            claim_msg(stage, 10_000, 0, 0, 10 * day),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("alice".to_string()),
+                Uint128(4_000),
+                Some("Pay Claim amount".to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()]
+        );
+
+        let escrow: EscrowBalanceResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::EscrowBalance {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(escrow.total_funded, Uint128(5_000));
+        assert_eq!(escrow.total_claimed, Uint128(4_000));
+        assert_eq!(escrow.balance, Uint128(1_000));
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 5 * day),
+            claim_msg(stage, 10_000, 0, 0, 10 * day),
+        ));
+        assert_eq!(
+            error,
+            "Insufficient Funds: Contract balance: 1000 does not cover the required amount: 2000"
+        );
+    }
+
+    #[test]
+    fn test_handle_emergency_withdraw() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 0, START_AT, 0, 10 * day);
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                custody: Some(true),
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        let stage = ReadonlyConfig::from_storage(&deps.storage).current_stage();
+        handle(
+            &mut deps,
+            mock_env("token", &[]),
+            HandleMsg::Receive {
+                sender: HumanAddr("treasury".to_string()),
+                from: HumanAddr("treasury".to_string()),
+                amount: Uint128(5_000),
+                memo: None,
+                msg: Some(
+                    to_binary(&ReceiveMsg::FundRound {
+                        stage: Uint128::from(stage),
+                    })
+                    .unwrap(),
+                ),
+            },
+        )
+        .unwrap();
+
+        let withdraw_msg = |token: &str| HandleMsg::EmergencyWithdraw {
+            stage: Uint128::from(stage),
+            token: token.to_string(),
+            amount: Uint128(3_000),
+            recipient: HumanAddr("treasury".to_string()),
+            padding: None,
+        };
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            withdraw_msg("token"),
+        ));
+        assert_eq!(
+            error,
+            "Emergency withdrawals are only allowed while the contract is stopped"
+        );
+
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetContractStatus {
+                level: ContractStatusLevel::StopAll,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            withdraw_msg("token"),
+        ));
+        assert_eq!(
+            error,
+            "This is an admin command. Admin commands can only be run from admin address"
+        );
+        let error = extract_error_msg(handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            withdraw_msg("other_token"),
+        ));
+        assert_eq!(
+            error,
+            format!("Round {} does not hold token other_token", stage)
+        );
+
+        let res = handle(
+            &mut deps,
+            env_at("admin", START_AT + day),
+            withdraw_msg("token"),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("treasury".to_string()),
+                Uint128(3_000),
+                Some(format!("Emergency withdrawal from vesting round {}", stage)),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()]
+        );
+
+        let escrow: EscrowBalanceResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::EscrowBalance {
+                    stage: Uint128::from(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(escrow.total_withdrawn, Uint128(3_000));
+        assert_eq!(escrow.balance, Uint128(2_000));
+
+        let withdrawals: EmergencyWithdrawalsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::EmergencyWithdrawals {
+                    stage: Uint128::from(stage),
+                    page: None,
+                    page_size: 10,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(withdrawals.total, 1);
+        assert_eq!(
+            withdrawals.withdrawals[0],
+            WithdrawalTx {
+                id: 1,
+                stage: Uint128::from(stage),
+                token: "token".to_string(),
+                amount: Uint128(3_000),
+                recipient: HumanAddr("treasury".to_string()),
+                block_time: START_AT + day,
+                block_height: 12_345 + day / 6,
+            }
+        );
+
+        let error = extract_error_msg(query(
+            &deps,
+            QueryMsg::EmergencyWithdrawals {
+                stage: Uint128::from(stage),
+                page: Some(u32::MAX),
+                page_size: 10,
+            },
+        ));
+        assert_eq!(error, "Page out of range!");
+    }
+
+    #[test]
+    fn test_handle_claim_insufficient_allowance() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+        let distribution = HumanAddr("distribution".to_string());
+
+        deps.querier
+            .allowances
+            .insert(distribution.clone(), (500, None));
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(
+            error,
+            "Insufficient Funds: Contract balance: 500 does not cover the required amount: 1900"
+        );
+
+        deps.querier
+            .allowances
+            .insert(distribution, (1_000_000, Some(START_AT)));
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        ));
+        assert_eq!(
+            error,
+            "Insufficient Funds: Contract balance: 0 does not cover the required amount: 1900"
+        );
+    }
+
+    #[test]
+    fn test_handle_claim_from_distribution_wallet() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+        let distribution = HumanAddr("distribution".to_string());
+
+        // The distribution wallet keeps its own viewing key, the contract key can't read its balance
+        let contract_key = ReadonlyConfig::from_storage(&deps.storage)
+            .contract_viewing_key()
+            .unwrap();
+        assert_ne!(deps.querier.viewing_keys[&distribution], contract_key.0);
+        deps.querier
+            .allowances
+            .insert(distribution.clone(), (5_000, Some(START_AT + day)));
+
+        let status: RoundFundingStatusResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::RoundFundingStatus {
+                    stage: Uint128(stage),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(status.available, Uint128(5_000));
+        assert_eq!(status.allowance_expiration, Some(START_AT + day));
+
+        let result = handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+        let transfer = |amount: u128, memo: &str| {
+            snip20::transfer_from_msg(
+                distribution.clone(),
+                HumanAddr("alice".to_string()),
+                Uint128(amount),
+                Some(memo.to_string()),
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            result.messages,
+            vec![
+                transfer(1_000, "Pay TGE"),
+                transfer(900, "Pay Claim amount")
+            ]
+        );
+    }
+
+    // Query tests
+
+    #[test]
+    fn test_query_round_funding_status() {
+        let mut deps = init_helper();
+        let leaf = leaf_hash("alice", 1, 10_000, 0, START_AT, 0, 0);
+        handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                total_allocation: Some(Uint128(10_000)),
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+        deps.querier.allowances.insert(
+            HumanAddr("distribution".to_string()),
+            (4_000, Some(START_AT + 100)),
+        );
+
+        let status: RoundFundingStatusResponse =
+            from_binary(&query(&deps, QueryMsg::RoundFundingStatus { stage: Uint128(1) }).unwrap())
+                .unwrap();
+        assert_eq!(
+            status,
+            RoundFundingStatusResponse {
+                stage: Uint128(1),
+                total_allocation: Some(Uint128(10_000)),
+                total_claimed: Uint128::zero(),
+                outstanding: Some(Uint128(10_000)),
+                available: Uint128(4_000),
+                allowance_expiration: Some(START_AT + 100),
+                is_covered: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn test_query_claim_history() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 0, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 1),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", START_AT + 3 * day),
+            claim_msg(stage, 10_000, 1_000, 0, 10 * day),
+        )
+        .unwrap();
+
+        let query_msg = QueryMsg::ClaimHistory {
+            address: HumanAddr("alice".to_string()),
+            key: "alice_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let error = extract_error_msg(query(&deps, query_msg.clone()));
+        assert_eq!(
+            error,
+            "Wrong viewing key for this address or viewing key not set"
+        );
+
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let history: ClaimHistoryResponse = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        let first_epoch = START_AT / day;
+        assert_eq!(history.total, 2);
+        // Latest claim is returned first
+        assert_eq!(history.claims[0].id, 2);
+        assert_eq!(history.claims[0].tge_amount, Uint128::zero());
+        assert_eq!(history.claims[0].linear_amount, Uint128(2_700));
+        assert_eq!(history.claims[0].start_epoch, Some(first_epoch + 1));
+        assert_eq!(history.claims[0].end_epoch, Some(first_epoch + 3));
+        assert_eq!(history.claims[1].id, 1);
+        assert_eq!(history.claims[1].tge_amount, Uint128(1_000));
+        assert_eq!(history.claims[1].linear_amount, Uint128(900));
+        assert_eq!(history.claims[1].recipient, HumanAddr("alice".to_string()));
+        assert_eq!(history.claims[1].block_time, START_AT + 1);
+
+        let paged: ClaimHistoryResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::ClaimHistory {
+                    address: HumanAddr("alice".to_string()),
+                    key: "alice_key".to_string(),
+                    page: Some(1),
+                    page_size: 1,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(paged.claims, vec![history.claims[1].clone()]);
+        assert_eq!(paged.total, 2);
+
+        let error = extract_error_msg(query(
+            &deps,
+            QueryMsg::ClaimHistory {
+                address: HumanAddr("alice".to_string()),
+                key: "alice_key".to_string(),
+                page: Some(u32::MAX),
+                page_size: 2,
+            },
+        ));
+        assert_eq!(error, "Page out of range!");
+    }
+
+    #[test]
+    fn test_query_unlock_schedule() {
+        let mut deps = init_helper();
+        let day = ONE_DAY_IN_SECONDS;
+        let leaf = leaf_hash("alice", 1, 10_000, 1_000, START_AT, 2 * day, 10 * day);
+        let stage = register_round_helper(&mut deps, leaf);
+
+        let schedule: UnlockScheduleResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::UnlockSchedule {
+                    stage: Uint128::from(stage),
+                    amount: Uint128(10_000),
+                    tge: Uint128(1_000),
+                    start_at: START_AT,
+                    cliff: 2 * day,
+                    duration: 10 * day,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(schedule.tge_amount, Uint128(1_000));
+        assert_eq!(schedule.linear_amount, Uint128(9_000));
+        assert_eq!(schedule.events.len(), 12);
+        assert_eq!(schedule.events[0].kind, UnlockEventKind::CliffEnd);
+        assert_eq!(schedule.events[0].unlock_at, START_AT + 2 * day);
+        assert_eq!(schedule.events[1].kind, UnlockEventKind::Tge);
+        assert_eq!(schedule.events[1].unlock_at, START_AT + 2 * day + 1);
+
+        let first_epoch = (START_AT + 2 * day) / day;
+        let epoch = |index: u64| {
+            schedule
+                .events
+                .iter()
+                .find(|event| event.epoch_index == Some(index))
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(epoch(first_epoch).unlock_at, START_AT + 2 * day + 1);
+        assert_eq!(epoch(first_epoch + 1).unlock_at, (first_epoch + 1) * day);
+        assert_eq!(epoch(first_epoch + 9).cumulative_amount, Uint128(10_000));
+        assert_eq!(schedule.total_epochs, 10);
+        assert_eq!(schedule.last, Some(first_epoch + 9));
+
+        let schedule_query = |tge: u128, start_at: u64, start_after: Option<u64>, limit| {
+            query(
+                &deps,
+                QueryMsg::UnlockSchedule {
+                    stage: Uint128::from(stage),
+                    amount: Uint128(10_000),
+                    tge: Uint128(tge),
+                    start_at,
+                    cliff: 2 * day,
+                    duration: 10 * day,
+                    start_after,
+                    limit,
+                },
+            )
+        };
+
+        // Pages continue the cumulative amounts of the previous ones
+        let page: UnlockScheduleResponse =
+            from_binary(&schedule_query(1_000, START_AT, None, Some(4)).unwrap()).unwrap();
+        assert_eq!(page.events, schedule.events[..6].to_vec());
+        assert_eq!(page.last, Some(first_epoch + 3));
+        let page: UnlockScheduleResponse =
+            from_binary(&schedule_query(1_000, START_AT, page.last, Some(4)).unwrap()).unwrap();
+        assert_eq!(page.events, schedule.events[6..10].to_vec());
+        let page: UnlockScheduleResponse =
+            from_binary(&schedule_query(1_000, START_AT, page.last, Some(4)).unwrap()).unwrap();
+        assert_eq!(page.events, schedule.events[10..].to_vec());
+        assert_eq!(page.last, Some(first_epoch + 9));
+
+        let error = extract_error_msg(schedule_query(10_001, START_AT, None, None));
+        assert_eq!(error, "TGE can be at most 10000!");
+        let error = extract_error_msg(schedule_query(1_000, u64::MAX, None, None));
+        assert_eq!(error, "Invalid vesting schedule!");
+
+        let error = extract_error_msg(handle(
+            &mut deps,
+            env_at("alice", START_AT),
+            claim_msg(stage, 10_000, 1_000, 2 * day, 10 * day),
+        ));
+        assert_eq!(error, "Cliff time not passed!");
+
+        // Payouts follow the preview
+        let mut claimed = 0u128;
+        for time in &[START_AT + 2 * day + 1, START_AT + 5 * day] {
+            let res = handle(
+                &mut deps,
+                env_at("alice", *time),
+                claim_msg(stage, 10_000, 1_000, 2 * day, 10 * day),
+            )
+            .unwrap();
+            let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+            let HandleAnswer::Claim {
+                tge_amount,
+                claim_amount,
+                ..
+            } = answer
+            else {
+                panic!("Unexpected answer")
+            };
+            claimed += tge_amount.u128() + claim_amount.u128();
+
+            let unlocked = schedule
+                .events
+                .iter()
+                .rfind(|event| event.unlock_at <= *time)
+                .unwrap();
+            assert_eq!(Uint128(claimed), unlocked.cumulative_amount);
+        }
+
+        handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let position: UnlockScheduleResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::PositionUnlockSchedule {
+                    address: HumanAddr("alice".to_string()),
+                    key: "alice_key".to_string(),
+                    stage: Uint128::from(stage),
+                    grant: None,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(position.total_claimed, Some(Uint128(claimed)));
+        assert_eq!(position.events, schedule.events);
+    }
+
+    #[test]
+    fn test_query_contract_viewing_key() {
+        let mut deps = init_helper();
+        let leaf = leaf_hash("alice", 1, 10_000, 0, START_AT, 0, 0);
+        let res = handle(
+            &mut deps,
+            env_at("admin", START_AT),
+            HandleMsg::from(RoundMsg {
+                distribution: None,
+                merkle_root: Some(hex::encode(leaf)),
+                ..RoundMsg::default()
+            }),
+        )
+        .unwrap();
+
+        for (account, key) in &[("admin", "admin_key"), ("alice", "alice_key")] {
+            handle(
+                &mut deps,
+                mock_env(*account, &[]),
+                HandleMsg::SetViewingKey {
+                    key: key.to_string(),
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+
+        let error = extract_error_msg(query(
+            &deps,
+            QueryMsg::ContractViewingKey {
+                address: HumanAddr("alice".to_string()),
+                key: "alice_key".to_string(),
+            },
+        ));
+        assert_eq!(
+            error,
+            "This is an admin query. Admin queries can only be run from admin address"
+        );
+
+        let response: ContractViewingKeyResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::ContractViewingKey {
+                    address: HumanAddr("admin".to_string()),
+                    key: "admin_key".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_ne!(response.key, "Snip20-vesting");
+        assert_eq!(
+            res.messages[1],
+            snip20::set_viewing_key_msg(
+                response.key,
+                None,
+                256,
+                "token_hash".to_string(),
+                HumanAddr("token".to_string()),
+            )
+            .unwrap()
+        );
+    }
 }
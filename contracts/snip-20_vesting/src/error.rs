@@ -31,6 +31,9 @@ pub enum ContractError {
     #[error("Insufficient Funds: Contract balance: {balance} does not cover the required amount: {amount}")]
     InsufficientFunds { balance: Uint128, amount: Uint128 },
 
+    #[error("Claim does not match the leaf of the position opened for grant {grant} of stage {stage}")]
+    LeafMismatch { stage: Uint128, grant: u64 },
+
     #[error("Cannot migrate from different contract type: {previous_contract}")]
     CannotMigrate { previous_contract: String },
 
@@ -3,8 +3,18 @@ pub mod msg;
 pub mod state;
 pub mod utils;
 pub mod helpers;
+pub mod viewing_key;
+pub mod claim_history;
+pub mod allocations;
+pub mod positions;
+pub mod reconcile;
+pub mod withdrawals;
+pub mod staking;
+pub mod error;
 mod constants;
 mod merkle_proof;
+mod signature;
+mod rand;
 mod vesting;
 
 #[cfg(target_arch = "wasm32")]
@@ -1 +1,2 @@
-pub mod vesting_stats_verify;
\ No newline at end of file
+pub mod multiproof;
+pub mod vesting_stats_verify;
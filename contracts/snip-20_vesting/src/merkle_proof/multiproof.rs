@@ -0,0 +1,247 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{StdError, StdResult};
+use sha2::Digest;
+
+/// Proof that several leaves belong to the same tree, sharing the nodes their single
+/// proofs have in common.
+///
+/// The tree is the one `verify_user_vesting_stats` checks against: every parent is
+/// `sha256` of its two children sorted bytewise and concatenated. The encoding is the one
+/// of OpenZeppelin's `MerkleProof.multiProofVerify`, which `merkletreejs` produces with
+/// `getMultiProof` and `getProofFlags` on a `sortPairs` tree:
+///
+/// * the leaves are given in the order the generator returns them, which for a tree
+///   stored as an array is by descending position,
+/// * `proof` holds the hex encoded sibling hashes that can't be computed from the leaves,
+///   in the order they are consumed,
+/// * `proof_flags` has one entry per hash computed on the way to the root. `true` takes
+///   the second operand from the leaves and computed hashes, `false` takes it from `proof`.
+///
+/// `proof_flags.len()` is always `leaves + proof.len() - 1`. A single leaf with every
+/// flag set to `false` is the single proof of that leaf.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct MultiProof {
+    pub proof: Vec<String>,
+    pub proof_flags: Vec<bool>,
+}
+
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut hashes = [*a, *b];
+    hashes.sort_unstable();
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(sha2::Sha256::digest(&hashes.concat()).as_slice());
+    hash
+}
+
+/// Checks `leaves` against `merkle_root`, see `MultiProof` for the encoding
+pub fn verify_multiproof(
+    merkle_root: &str,
+    leaves: &[[u8; 32]],
+    multiproof: &MultiProof,
+) -> StdResult<()> {
+    let mut proof = Vec::with_capacity(multiproof.proof.len());
+    for p in multiproof.proof.iter() {
+        let mut proof_buf = [0u8; 32];
+        hex::decode_to_slice(p, &mut proof_buf)
+            .map_err(|_| StdError::generic_err("Invalid merkle proof"))?;
+        proof.push(proof_buf);
+    }
+
+    let total_hashes = multiproof.proof_flags.len();
+    if leaves.len() + proof.len() != total_hashes + 1 {
+        return Err(StdError::generic_err("Invalid merkle multiproof length!"));
+    }
+
+    // Leaves are consumed first, then the hashes computed from them, like a queue
+    let mut hashes: Vec<[u8; 32]> = Vec::with_capacity(total_hashes);
+    let (mut leaf_pos, mut hash_pos, mut proof_pos) = (0, 0, 0);
+    let next_node = |leaf_pos: &mut usize, hash_pos: &mut usize, hashes: &[[u8; 32]]| {
+        if *leaf_pos < leaves.len() {
+            *leaf_pos += 1;
+            Ok(leaves[*leaf_pos - 1])
+        } else if *hash_pos < hashes.len() {
+            *hash_pos += 1;
+            Ok(hashes[*hash_pos - 1])
+        } else {
+            Err(StdError::generic_err("Invalid merkle multiproof!"))
+        }
+    };
+
+    for flag in multiproof.proof_flags.iter() {
+        let a = next_node(&mut leaf_pos, &mut hash_pos, &hashes)?;
+        let b = if *flag {
+            next_node(&mut leaf_pos, &mut hash_pos, &hashes)?
+        } else {
+            proof_pos += 1;
+            *proof
+                .get(proof_pos - 1)
+                .ok_or_else(|| StdError::generic_err("Invalid merkle multiproof!"))?
+        };
+        hashes.push(hash_pair(&a, &b));
+    }
+
+    let root = match (hashes.last(), leaves.first(), proof.first()) {
+        (Some(hash), _, _) => *hash,
+        (None, Some(leaf), _) => *leaf,
+        (None, None, Some(hash)) => *hash,
+        (None, None, None) => return Err(StdError::generic_err("Invalid merkle multiproof!")),
+    };
+
+    let mut root_buf: [u8; 32] = [0; 32];
+    hex::decode_to_slice(merkle_root, &mut root_buf)
+        .map_err(|_| StdError::generic_err("Invalid merkle tree validation!"))?;
+
+    if root_buf != root {
+        return Err(StdError::generic_err("Proof verification failed!"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_proof::vesting_stats_verify::{user_vesting_leaf, verify_user_vesting_stats};
+    use crate::rand::Prng;
+    use cosmwasm_std::HumanAddr;
+
+    /// Tree stored as an array like OpenZeppelin's `StandardMerkleTree`, the root at 0 and
+    /// the leaves at the end in reverse order
+    fn build_tree(leaves: &[[u8; 32]]) -> Vec<[u8; 32]> {
+        let len = 2 * leaves.len() - 1;
+        let mut tree = vec![[0u8; 32]; len];
+        for (i, leaf) in leaves.iter().enumerate() {
+            tree[len - 1 - i] = *leaf;
+        }
+        for i in (0..len - leaves.len()).rev() {
+            tree[i] = hash_pair(&tree[2 * i + 1], &tree[2 * i + 2]);
+        }
+        tree
+    }
+
+    fn sibling(i: usize) -> usize {
+        if i % 2 == 1 {
+            i + 1
+        } else {
+            i - 1
+        }
+    }
+
+    fn single_proof(tree: &[[u8; 32]], mut i: usize) -> Vec<String> {
+        let mut proof = vec![];
+        while i > 0 {
+            proof.push(hex::encode(tree[sibling(i)]));
+            i = (i - 1) / 2;
+        }
+        proof
+    }
+
+    /// Same algorithm as `StandardMerkleTree.getMultiProof`
+    fn multiproof(tree: &[[u8; 32]], mut indices: Vec<usize>) -> (Vec<[u8; 32]>, MultiProof) {
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices.dedup();
+        let leaves = indices.iter().map(|i| tree[*i]).collect();
+
+        let mut queue = std::collections::VecDeque::from(indices);
+        let mut proof = vec![];
+        let mut proof_flags = vec![];
+        while let Some(&j) = queue.front() {
+            if j == 0 {
+                break;
+            }
+            queue.pop_front();
+            let s = sibling(j);
+            if queue.front() == Some(&s) {
+                proof_flags.push(true);
+                queue.pop_front();
+            } else {
+                proof_flags.push(false);
+                proof.push(hex::encode(tree[s]));
+            }
+            queue.push_back((j - 1) / 2);
+        }
+        if proof_flags.is_empty() && queue.is_empty() {
+            proof.push(hex::encode(tree[0]));
+        }
+
+        (leaves, MultiProof { proof, proof_flags })
+    }
+
+    #[test]
+    fn test_multiproof_matches_single_proofs() {
+        let mut rng = Prng::new(b"merkle proof", b"multiproof");
+
+        for size in 1..=17usize {
+            let grants: Vec<(HumanAddr, u128)> = (0..size)
+                .map(|i| (HumanAddr(format!("user{}", i)), 1_000 * (i as u128 + 1)))
+                .collect();
+            let leaves: Vec<[u8; 32]> = grants
+                .iter()
+                .map(|(user, amount)| user_vesting_leaf(user, 1, 0, *amount, 100, 0, 0, 86_400))
+                .collect();
+            let tree = build_tree(&leaves);
+            let root = hex::encode(tree[0]);
+            let leaf_index = |i: usize| tree.len() - 1 - i;
+
+            for (i, (user, amount)) in grants.iter().enumerate() {
+                let proof = single_proof(&tree, leaf_index(i));
+                assert_eq!(
+                    verify_user_vesting_stats(
+                        &root,
+                        proof.clone(),
+                        user.clone(),
+                        1,
+                        0,
+                        *amount,
+                        100,
+                        0,
+                        0,
+                        86_400
+                    ),
+                    Ok(true)
+                );
+
+                // A single proof is a multiproof of one leaf taking every sibling from the proof
+                let (leaves, multi) = multiproof(&tree, vec![leaf_index(i)]);
+                assert_eq!(multi.proof, proof);
+                assert!(multi.proof_flags.iter().all(|flag| !flag));
+                assert_eq!(verify_multiproof(&root, &leaves, &multi), Ok(()));
+            }
+
+            for _ in 0..20 {
+                let indices: Vec<usize> = (0..size)
+                    .filter(|_| rng.rand_bytes()[0] < 128)
+                    .map(leaf_index)
+                    .collect();
+                let (leaves, multi) = multiproof(&tree, indices.clone());
+                assert_eq!(verify_multiproof(&root, &leaves, &multi), Ok(()));
+
+                if leaves.is_empty() {
+                    continue;
+                }
+
+                // Shared nodes are only sent once
+                let single_nodes: usize =
+                    indices.iter().map(|i| single_proof(&tree, *i).len()).sum();
+                assert!(multi.proof.len() <= single_nodes);
+
+                let mut tampered = leaves.clone();
+                tampered[0][0] ^= 1;
+                assert_eq!(
+                    verify_multiproof(&root, &tampered, &multi),
+                    Err(StdError::generic_err("Proof verification failed!"))
+                );
+                let mut padded = multi.clone();
+                padded.proof_flags.push(false);
+                assert_eq!(
+                    verify_multiproof(&root, &leaves, &padded),
+                    Err(StdError::generic_err("Invalid merkle multiproof length!"))
+                );
+            }
+        }
+    }
+}
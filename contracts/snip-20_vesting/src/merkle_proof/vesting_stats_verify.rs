@@ -1,41 +1,71 @@
-use cosmwasm_std::{HumanAddr, StdError, Storage, Api, Querier, Extern};
+use cosmwasm_std::{HumanAddr, StdError};
 use sha2::Digest;
 
 use std::convert::{TryInto};
-use crate::state::ReadonlyVestingRound;
 
-pub fn verify_user_vesting_stats<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+/// Leaf of a grant in the merkle tree of a round.
+///
+/// The first grant of a user in a stage has index 0 and keeps the original leaf format,
+/// further grants append `:<grant>` so their leaves can't collide with it.
+#[allow(clippy::too_many_arguments)]
+pub fn user_vesting_leaf(
+    user_addr: &HumanAddr,
+    stage: u128,
+    grant: u64,
+    total_amount: u128,
+    tge: u128,
+    start_at: u64,
+    cliff: u64,
+    duration: u64,
+) -> [u8; 32] {
+    let mut user_input = format!(
+        "{}{}{}{}{}{}{}",
+        user_addr,
+        stage,
+        total_amount,
+        tge,
+        start_at,
+        duration,
+        cliff
+    );
+    if grant > 0 {
+        user_input.push_str(&format!(":{}", grant));
+    }
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(sha2::Sha256::digest(user_input.as_bytes()).as_slice());
+    hash
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn verify_user_vesting_stats(
+    merkle_root: &str,
     proof: Vec<String>,
     user_addr: HumanAddr,
     stage: u128,
+    grant: u64,
     total_amount: u128,
     tge: u128,
     start_at: u64,
     cliff: u64,
     duration: u64,
 ) -> Result<bool, StdError> {
-    let user_input = format!(
-        "{}{}{}{}{}{}{}",
-        user_addr.to_string(),
+    let hash = user_vesting_leaf(
+        &user_addr,
         stage,
+        grant,
         total_amount,
         tge,
         start_at,
+        cliff,
         duration,
-        cliff
     );
     
-    let hash = sha2::Sha256::digest(user_input.as_bytes())
-        .as_slice()
-        .try_into()
-        .map_err(|_| StdError::generic_err("Wrong Length!"))?;
-    
     let hash = proof.into_iter().try_fold(hash, |hash, p| {
         let mut proof_buf = [0; 32];
         let is_valid_merkle_proof = hex::decode_to_slice(p, &mut proof_buf);
     
-        if !is_valid_merkle_proof.is_ok() {
+        if is_valid_merkle_proof.is_err() {
             return Err(StdError::generic_err("Invalid merkle proof"));
         }
     
@@ -47,13 +77,10 @@ pub fn verify_user_vesting_stats<S: Storage, A: Api, Q: Querier>(
             .map_err(|_| StdError::generic_err("Wrong Length!"))
     })?;
     
-    let read_vesting_round = ReadonlyVestingRound::from_storage(&deps.storage);
-    let merkle_root = read_vesting_round.config_by_stage(stage)?.merkle_root;
-    
     let mut root_buf: [u8; 32] = [0; 32];
-    let is_valid_merkle_root = hex::decode_to_slice(&merkle_root, &mut root_buf);
+    let is_valid_merkle_root = hex::decode_to_slice(merkle_root, &mut root_buf);
     
-    if !is_valid_merkle_root.is_ok() {
+    if is_valid_merkle_root.is_err() {
         return Err(StdError::generic_err("Invalid merkle tree validation!"));
     }
     
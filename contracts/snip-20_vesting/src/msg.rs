@@ -1,13 +1,19 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{HumanAddr, Uint128};
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
 
-use crate::constants::ContractStatusLevel;
+use crate::{allocations::Allocation, claim_history::ClaimTx, positions::{PositionImport, PositionRecord}, reconcile::Discrepancy, withdrawals::WithdrawalTx, vesting::unlock_schedule::UnlockEvent, constants::ContractStatusLevel, state::{Allocator, EpochAnchor, RoundMetadata}, utils::Duration, viewing_key::ViewingKey};
+pub use crate::merkle_proof::multiproof::MultiProof;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
     pub owner: Option<HumanAddr>,
-    pub contract_status: Option<u8>
+    pub contract_status: Option<u8>,
+    /// Entropy of the contract viewing key, only used at init
+    pub prng_seed: Binary,
+    /// Keep amounts out of the plaintext logs of every handle, claims report theirs in the
+    /// encrypted data only
+    pub private_amounts: Option<bool>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -17,37 +23,356 @@ pub enum QueryMsg {
         stage: Uint128
     },
     GetCurrentStage {},
+    /// Every round, newest first
+    VestingRounds {
+        page: Option<u32>,
+        /// Capped to `MAX_PAGE_SIZE`
+        page_size: u32,
+    },
+    EscrowBalance {
+        stage: Uint128
+    },
+    RoundFundingStatus {
+        stage: Uint128
+    },
+    /// Claim activity of a round
+    RoundStats {
+        stage: Uint128
+    },
+    /// Staked escrow and rewards of a round that stakes its escrow
+    StakingStatus {
+        stage: Uint128
+    },
     ContractOwner {},
-    GrantedContractOwner {}
+    GrantedContractOwner {},
+    ContractStatus {},
+    /// Grants of an explicit round, in the order they were added
+    Allocations {
+        stage: Uint128,
+        page: Option<u32>,
+        /// Capped to `MAX_PAGE_SIZE`
+        page_size: u32,
+    },
+    ClaimHistory {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        /// Capped to `MAX_PAGE_SIZE`
+        page_size: u32,
+    },
+    EmergencyWithdrawals {
+        stage: Uint128,
+        page: Option<u32>,
+        /// Capped to `MAX_PAGE_SIZE`
+        page_size: u32,
+    },
+    /// Unlock timetable of a grant, given the fields of its merkle leaf. Epochs are listed
+    /// page by page, at most `MAX_PAGE_SIZE` at a time
+    UnlockSchedule {
+        stage: Uint128,
+        amount: Uint128,
+        tge: Uint128,
+        start_at: u64,
+        cliff: u64,
+        duration: u64,
+        /// Epoch index the previous page ended with
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Unlock timetable of the position opened by `address` in `stage`
+    PositionUnlockSchedule {
+        address: HumanAddr,
+        key: String,
+        stage: Uint128,
+        /// Defaults to the first grant
+        grant: Option<u64>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Viewing key the contract registered on round tokens, only available to the contract owner
+    ContractViewingKey {
+        address: HumanAddr,
+        key: String,
+    },
+    /// Checks the positions of a round page by page, only available to the contract owner.
+    /// `claimed_before` carries the `claimed_through` of the previous page, the round total
+    /// is checked on the last page
+    Reconcile {
+        address: HumanAddr,
+        key: String,
+        stage: Uint128,
+        start_after: Option<u64>,
+        /// At most `MAX_PAGE_SIZE`, which is also the default
+        limit: Option<u32>,
+        claimed_before: Option<Uint128>,
+    },
+    /// Every position of a round, in the order they were opened, only available to the
+    /// contract owner
+    ExportPositions {
+        address: HumanAddr,
+        key: String,
+        stage: Uint128,
+        start_after: Option<u64>,
+        /// At most `MAX_PAGE_SIZE`, which is also the default
+        limit: Option<u32>,
+    }
 }
 
+impl QueryMsg {
+    pub fn get_validation_params(&self) -> (Vec<&HumanAddr>, ViewingKey) {
+        match self {
+            Self::ClaimHistory { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::ContractViewingKey { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::PositionUnlockSchedule { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::Reconcile { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::ExportPositions { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            _ => panic!("This query type does not require authentication"),
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)] // Deserialized once per call
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
     RegisterNewVestingRound {
+        /// Wallet paying the claims of a round not in custody through its allowance to the
+        /// contract, it never has to share its viewing key
         distribution: Option<HumanAddr>,
         token_address: Option<HumanAddr>,
         token_code_hash: Option<String>,
+        denom: Option<String>,
+        custody: Option<bool>,
+        total_allocation: Option<Uint128>,
+        /// Vesting epoch, one day by default. `Height` makes grants use block heights
+        epoch: Option<Duration>,
+        /// Defaults to `calendar`
+        epoch_anchor: Option<EpochAnchor>,
         is_paused: Option<bool>,
-        merkle_root: String
+        /// Leaves the round inactive, so that positions can be carried over with
+        /// `ImportPositions` before `ActivateRound` lets claims in
+        import_positions: Option<bool>,
+        /// Root of the grants tree, for rounds claimed with merkle proofs
+        merkle_root: Option<String>,
+        /// Key signing the grants, for rounds claimed with signatures
+        allocator: Option<Allocator>,
+        /// Grants are added by the admin with `AddAllocations`
+        explicit: Option<bool>,
+        metadata: Option<RoundMetadata>,
+        /// Fee taken from every payout, none by default
+        fee: Option<RoundFee>,
+        /// Delegates the escrow of a custody round to a staking contract, positions earn
+        /// the rewards of their unvested amount
+        staking: Option<RoundStaking>,
+        padding: Option<String>,
+    },
+    FundRound {
+        stage: Uint128,
+        padding: Option<String>,
+    },
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        msg: Option<Binary>,
     },
     SetContractStatus {
         level: ContractStatusLevel,
-        // padding: Option<String>,
+        padding: Option<String>,
+    },
+    /// Replaces the metadata of a round
+    SetRoundMetadata {
+        stage: Uint128,
+        metadata: RoundMetadata,
+        padding: Option<String>,
+    },
+    SetPrivateAmounts {
+        enabled: bool,
+        padding: Option<String>,
     },
     GrantContractOwner {
-        new_admin: HumanAddr
+        new_admin: HumanAddr,
+        padding: Option<String>,
+    },
+    ClaimContractOwner {
+        padding: Option<String>,
+    },
+    RevokeGrantedContractOwner {
+        padding: Option<String>,
     },
-    ClaimContractOwner {},
-    RevokeGrantedContractOwner {},
     Claim {
-        proof: Vec<String>,
+        /// Merkle proof of the grant, empty if omitted
+        proof: Option<Vec<String>>,
+        /// Allocator signature of the grant, for signature rounds
+        signature: Option<Binary>,
         stage: Uint128,
+        /// Index of the grant among the claimer's grants in this stage, 0 by default
+        grant: Option<u64>,
         amount: Uint128,
         tge: Uint128,
         start_at: u64,
         cliff: u64,
         duration: u64,
+        /// Pays out with a SNIP-20 `Send` carrying this message to the claimer's `Receive`
+        /// hook, instead of the default message of the position
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    /// Sets the message claims of a position are sent with when they don't give one,
+    /// `None` goes back to plain transfers
+    SetClaimMsg {
+        stage: Uint128,
+        grant: Option<u64>,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    /// Writes grants of an explicit round, at most `MAX_ALLOCATIONS_PER_MSG` at a time
+    AddAllocations {
+        stage: Uint128,
+        allocations: Vec<Allocation>,
+        padding: Option<String>,
+    },
+    /// Lets claims in a round registered with `import_positions`, and closes its imports
+    ActivateRound {
+        stage: Uint128,
+        padding: Option<String>,
+    },
+    /// Pauses or resumes the claims of a round
+    SetRoundPaused {
+        stage: Uint128,
+        is_paused: bool,
+        padding: Option<String>,
+    },
+    /// Carries positions over from another vesting contract, before the round is activated
+    ImportPositions {
+        stage: Uint128,
+        positions: Vec<PositionImport>,
+        /// Proves the grants of all `positions` at once in a merkle round, in place of their
+        /// own proofs. The positions come in the order of the multiproof leaves
+        multiproof: Option<MultiProof>,
+        padding: Option<String>,
+    },
+    /// Sends the staking rewards earned by escrow no position held to `recipient`
+    SweepStakingRewards {
+        stage: Uint128,
+        recipient: HumanAddr,
+        padding: Option<String>,
+    },
+    /// Moves escrowed funds out of a custody round, only while the contract is stopped
+    EmergencyWithdraw {
+        stage: Uint128,
+        /// Token contract address or native denom of the round
+        token: String,
+        amount: Uint128,
+        recipient: HumanAddr,
+        padding: Option<String>,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    RegisterNewVestingRound {
+        status: ResponseStatus,
+        stage: Uint128,
+    },
+    FundRound {
+        status: ResponseStatus,
+    },
+    Receive {
+        status: ResponseStatus,
+    },
+    SetContractStatus {
+        status: ResponseStatus,
+    },
+    SetRoundMetadata {
+        status: ResponseStatus,
+    },
+    SetPrivateAmounts {
+        status: ResponseStatus,
+    },
+    GrantContractOwner {
+        status: ResponseStatus,
+    },
+    ClaimContractOwner {
+        status: ResponseStatus,
+    },
+    RevokeGrantedContractOwner {
+        status: ResponseStatus,
+    },
+    Claim {
+        status: ResponseStatus,
+        tge_amount: Uint128,
+        claim_amount: Uint128,
+        /// Part of the payout sent to the fee recipient, only for rounds with a fee
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fee_amount: Option<Uint128>,
+        /// Staking rewards paid with the claim, only for rounds that stake their escrow
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reward_amount: Option<Uint128>,
+    },
+    SetClaimMsg {
+        status: ResponseStatus,
+    },
+    SetViewingKey {
+        status: ResponseStatus,
+    },
+    AddAllocations {
+        status: ResponseStatus,
+    },
+    ActivateRound {
+        status: ResponseStatus,
+    },
+    SetRoundPaused {
+        status: ResponseStatus,
+    },
+    ImportPositions {
+        status: ResponseStatus,
+    },
+    EmergencyWithdraw {
+        status: ResponseStatus,
+    },
+    SweepStakingRewards {
+        status: ResponseStatus,
+        amount: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    Success,
+    Failure,
+}
+
+/// Fee taken from every payout of a round and sent to `recipient`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RoundFee {
+    /// Fee in basis points of each payout
+    pub rate_bps: u16,
+    pub recipient: HumanAddr
+}
+
+/// Staking contract a round delegates its escrow to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RoundStaking {
+    pub address: HumanAddr,
+    pub code_hash: String
+}
+
+/// Message embedded in a SNIP-20 `Send` to the vesting contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    FundRound {
+        stage: Uint128
     }
 }
 
@@ -56,13 +381,179 @@ pub enum HandleMsg {
 pub struct VestingRoundResponse {
     pub stage: Uint128,
     pub total_claimed: Uint128,
-    pub token_address: String,
-    pub merkle_root: String,
+    pub total_funded: Uint128,
+    pub total_withdrawn: Uint128,
+    pub token_address: Option<HumanAddr>,
+    pub denom: Option<String>,
+    pub custody: bool,
+    pub epoch: Duration,
+    pub epoch_anchor: EpochAnchor,
+    pub is_paused: bool,
+    pub activated: bool,
+    pub fee: Option<RoundFee>,
+    pub staking: Option<RoundStaking>,
+    pub merkle_root: Option<String>,
+    pub allocator: Option<Allocator>,
+    /// Sum of the grants of an explicit round
+    pub total_allocated: Option<Uint128>,
+    pub metadata: Option<RoundMetadata>,
     pub created_at: u64
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct StakingStatusResponse {
+    pub stage: Uint128,
+    pub staking: RoundStaking,
+    pub total_staked: Uint128,
+    /// Rewards earned by a staked token, scaled by 10^12
+    pub reward_per_token: Uint128,
+    pub rewards_harvested: Uint128,
+    pub rewards_paid: Uint128,
+    /// Rewards of escrow no position held, the owner can sweep them
+    pub unassigned_rewards: Uint128,
+    pub rewards_swept: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RoundStatsResponse {
+    pub stage: Uint128,
+    /// Positions opened by a first claim or imported
+    pub positions: u64,
+    /// Positions that received their whole grant
+    pub fully_vested_positions: u64,
+    pub tge_paid: Uint128,
+    pub linear_paid: Uint128,
+    /// Claims that paid out something
+    pub claim_count: u64,
+    pub first_claim_at: Option<u64>,
+    pub last_claim_at: Option<u64>,
+    pub imported_amount: Uint128,
+    pub imported_claimed: Uint128,
+    pub fees_paid: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ReconcileResponse {
+    pub stage: Uint128,
+    pub total_positions: u64,
+    /// Index of the last position checked, the `start_after` of the next page
+    pub last: Option<u64>,
+    /// Claimed by the positions of this page and of the previous ones
+    pub claimed_through: Uint128,
+    pub discrepancies: Vec<Discrepancy>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ExportPositionsResponse {
+    pub stage: Uint128,
+    pub positions: Vec<PositionRecord>,
+    pub total: u64,
+    /// Index of the last position returned, the `start_after` of the next page
+    pub last: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct VestingRoundsResponse {
+    pub rounds: Vec<VestingRoundResponse>,
+    pub total: u64
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct ContractOwnerResponse {
     pub contract_owner: HumanAddr
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ContractStatusResponse {
+    pub status: ContractStatusLevel
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ClaimHistoryResponse {
+    pub claims: Vec<ClaimTx>,
+    pub total: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct EscrowBalanceResponse {
+    pub stage: Uint128,
+    pub total_funded: Uint128,
+    pub total_claimed: Uint128,
+    pub total_withdrawn: Uint128,
+    pub balance: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct UnlockScheduleResponse {
+    pub stage: Uint128,
+    pub tge_amount: Uint128,
+    pub linear_amount: Uint128,
+    /// Amount already paid to the position, only set for `PositionUnlockSchedule`
+    pub total_claimed: Option<Uint128>,
+    pub events: Vec<UnlockEvent>,
+    /// Epochs in the whole schedule
+    pub total_epochs: u64,
+    /// Index of the last epoch returned, the `start_after` of the next page
+    pub last: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct AllocationsResponse {
+    pub allocations: Vec<Allocation>,
+    pub total: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct EmergencyWithdrawalsResponse {
+    pub withdrawals: Vec<WithdrawalTx>,
+    pub total: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct ContractViewingKeyResponse {
+    pub key: String
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct RoundFundingStatusResponse {
+    pub stage: Uint128,
+    pub total_allocation: Option<Uint128>,
+    pub total_claimed: Uint128,
+    /// Allocations not paid out yet, here or by the contract imported positions come from,
+    /// known only when the round declares its total allocation
+    pub outstanding: Option<Uint128>,
+    /// Funds the contract can currently pay out for this round, for rounds that are not in
+    /// custody the allowance of the distribution wallet, which can exceed what the wallet holds
+    pub available: Uint128,
+    /// Expiration of the distribution allowance for rounds that are not in custody
+    pub allowance_expiration: Option<u64>,
+    pub is_covered: Option<bool>
+}
+
+// Take a Vec<u8> and pad it up to a multiple of `block_size`, using spaces at the end.
+pub fn space_pad(block_size: usize, message: &mut Vec<u8>) -> &mut Vec<u8> {
+    let len = message.len();
+    let surplus = len % block_size;
+    if surplus == 0 {
+        return message;
+    }
+
+    let missing = block_size - surplus;
+    message.reserve(missing);
+    message.resize(len + missing, b' ');
+    message
+}
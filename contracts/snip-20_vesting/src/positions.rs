@@ -0,0 +1,142 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    Binary, CanonicalAddr, HumanAddr, ReadonlyStorage, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+use crate::constants::MAX_PAGE_SIZE;
+use crate::state::UserVestingStatsState;
+
+const PREFIX_POSITION_INDEX: &[u8] = b"position_index";
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionStatus {
+    /// Nothing was paid from the linear schedule yet
+    Opened,
+    Vesting,
+    FullyVested,
+}
+
+/// A position with the fields of the leaf that opened it, as exported by `ExportPositions`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PositionRecord {
+    pub address: HumanAddr,
+    pub grant: u64,
+    /// Amount of the grant, TGE included
+    pub amount: Uint128,
+    pub tge: Uint128,
+    pub start_at: u64,
+    pub cliff: u64,
+    pub duration: u64,
+    pub leaf_hash: Option<Binary>,
+    pub total_claimed: Uint128,
+    pub tge_amount: Uint128,
+    pub next_claim_epoch_index: u64,
+    pub status: PositionStatus,
+}
+
+impl PositionRecord {
+    pub fn from_stats(address: HumanAddr, stats: &UserVestingStatsState) -> Self {
+        let status = if stats.linear_claimed() >= stats.total_amount.u128() {
+            PositionStatus::FullyVested
+        } else if stats.linear_claimed() > 0 {
+            PositionStatus::Vesting
+        } else {
+            PositionStatus::Opened
+        };
+
+        PositionRecord {
+            address,
+            grant: stats.grant,
+            amount: Uint128::from(stats.total_amount.u128() + stats.tge_amount.u128()),
+            tge: stats.tge,
+            start_at: stats.start_vesting_epoch,
+            cliff: stats.cliff,
+            duration: stats.vesting_duration,
+            leaf_hash: stats.leaf_hash.clone(),
+            total_claimed: stats.total_claimed,
+            tge_amount: stats.tge_amount,
+            next_claim_epoch_index: stats.next_claim_epoch_index,
+            status,
+        }
+    }
+}
+
+/// Position carried over from another contract, with the proof of its grant the round
+/// allocation mode asks for
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PositionImport {
+    /// Position as returned by `ExportPositions`, `leaf_hash` and `status` are recomputed
+    /// for the round it is imported into
+    pub position: PositionRecord,
+    pub proof: Option<Vec<String>>,
+    pub signature: Option<Binary>,
+}
+
+// Positions are stored per user, this index lists the positions of each stage in the
+// order they were opened, so a whole round can be walked page by page.
+
+pub struct IndexedPosition {
+    /// Place of the position in the index of its stage
+    pub index: u64,
+    pub user: CanonicalAddr,
+    pub grant: u64,
+}
+
+pub fn index_position<S: Storage>(
+    store: &mut S,
+    stage: u128,
+    user: &CanonicalAddr,
+    grant: u64,
+) -> StdResult<()> {
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_POSITION_INDEX, &stage.to_be_bytes()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(&(user.clone(), grant))
+}
+
+/// Positions of `stage` following the `start_after` index entry, at most `MAX_PAGE_SIZE` of
+/// them, and the number of indexed positions
+pub fn get_indexed_positions<S: ReadonlyStorage>(
+    storage: &S,
+    stage: u128,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<(Vec<IndexedPosition>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_POSITION_INDEX, &stage.to_be_bytes()],
+        storage,
+    );
+
+    // Rounds nobody claimed from have no index yet
+    let store = AppendStore::<(CanonicalAddr, u64), _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let start = start_after.map_or(0, |index| index + 1);
+    let positions: StdResult<Vec<IndexedPosition>> = store
+        .iter()
+        .enumerate()
+        .skip(start as _)
+        .take(limit.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE) as _)
+        .map(|(index, position)| {
+            let (user, grant) = position?;
+            Ok(IndexedPosition {
+                index: index as u64,
+                user,
+                grant,
+            })
+        })
+        .collect();
+    positions.map(|positions| (positions, store.len() as u64))
+}
@@ -0,0 +1,122 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{HumanAddr, StdResult, Uint128};
+
+use crate::state::{EpochAnchor, UserVestingStatsState};
+use crate::vesting::calc_vesting_schedule::{
+    calc_tge_amount, calc_vested_amount, first_claim_epoch_index,
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// The position was paid more than its linear amount
+    OverClaimed,
+    /// The TGE recorded on the position is not the one of its grant
+    TgeMismatch,
+    /// The linear amount claimed is not what the schedule vested before `next_claim_epoch_index`
+    EpochMismatch,
+    /// An indexed position can't be found
+    MissingPosition,
+    /// The sum claimed by the positions differs from the round `total_claimed`
+    RoundTotalMismatch,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    /// Owner of the position, unset for round level discrepancies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<HumanAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grant: Option<u64>,
+    pub expected: Uint128,
+    pub actual: Uint128,
+}
+
+impl Discrepancy {
+    pub fn of_position(
+        kind: DiscrepancyKind,
+        address: &HumanAddr,
+        grant: u64,
+        expected: u128,
+        actual: u128,
+    ) -> Self {
+        Discrepancy {
+            kind,
+            address: Some(address.clone()),
+            grant: Some(grant),
+            expected: Uint128::from(expected),
+            actual: Uint128::from(actual),
+        }
+    }
+}
+
+/// Checks a position against its own schedule, `total_amount` excludes the TGE once it is paid
+pub fn check_position(
+    address: &HumanAddr,
+    position: &UserVestingStatsState,
+    epoch_length: u64,
+    anchor: &EpochAnchor,
+) -> StdResult<Vec<Discrepancy>> {
+    let mut discrepancies = vec![];
+    let linear_amount = position.total_amount.u128();
+    let linear_claimed = position.linear_claimed();
+
+    if linear_claimed > linear_amount || position.total_claimed < position.tge_amount {
+        discrepancies.push(Discrepancy::of_position(
+            DiscrepancyKind::OverClaimed,
+            address,
+            position.grant,
+            linear_amount + position.tge_amount.u128(),
+            position.total_claimed.u128(),
+        ));
+    }
+
+    if position.tge.u128() > 0 {
+        let tge_amount = calc_tge_amount(
+            linear_amount + position.tge_amount.u128(),
+            position.tge.u128(),
+        )?;
+        if tge_amount != position.tge_amount.u128() {
+            discrepancies.push(Discrepancy::of_position(
+                DiscrepancyKind::TgeMismatch,
+                address,
+                position.grant,
+                tge_amount,
+                position.tge_amount.u128(),
+            ));
+        }
+    }
+
+    // Every claim pays what vested up to its epoch and moves past it
+    let first_epoch_index = first_claim_epoch_index(
+        position.start_vesting_epoch,
+        position.cliff,
+        epoch_length,
+        anchor,
+    );
+    let vested = match position.next_claim_epoch_index.checked_sub(1) {
+        Some(last_claimed_epoch) => calc_vested_amount(
+            last_claimed_epoch,
+            first_epoch_index,
+            position.vesting_duration,
+            linear_amount,
+            epoch_length,
+        )?,
+        None => 0,
+    };
+    if vested != linear_claimed {
+        discrepancies.push(Discrepancy::of_position(
+            DiscrepancyKind::EpochMismatch,
+            address,
+            position.grant,
+            vested,
+            linear_claimed,
+        ));
+    }
+
+    Ok(discrepancies)
+}
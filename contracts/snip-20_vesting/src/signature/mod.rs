@@ -0,0 +1 @@
+pub mod vesting_stats_verify;
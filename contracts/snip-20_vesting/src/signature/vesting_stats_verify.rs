@@ -0,0 +1,147 @@
+//! Verification of allocations signed by a round allocator.
+//!
+//! The allocator signs, off-chain, the SHA-256 hash of a message that starts with the
+//! `ALLOCATION_DOMAIN` tag and the vesting contract address, followed by the fields of
+//! the grant:
+//!
+//! `sha256(domain + len(contract_address) + contract_address + len(user) + user + stage
+//!     + grant + amount + tge + start_at + cliff + duration)`
+//!
+//! where lengths are 4 bytes, `stage`, `amount` and `tge` are 16 bytes and the other
+//! numbers 8 bytes, all big-endian. Every field has a fixed width or a length prefix, so two
+//! different grants can't produce the same message. Secp256k1 signatures are 64 bytes
+//! "compact" signatures of that hash, ed25519 signatures are 64 bytes raw signatures with
+//! the 32 bytes hash as the message. Binding the contract address keeps a signature from
+//! being replayed on another deployment that uses the same allocator key.
+
+use cosmwasm_std::{Api, Binary, HumanAddr, StdError, StdResult};
+use sha2::Digest;
+
+use crate::state::SignatureAlgorithm;
+
+/// Tag opening every signed allocation, so the signatures can't be mistaken for other
+/// messages signed with the same key
+pub const ALLOCATION_DOMAIN: &[u8] = b"snip20-vesting-allocation-v1";
+
+fn push_length_prefixed(message: &mut Vec<u8>, bytes: &[u8]) {
+    message.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    message.extend_from_slice(bytes);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn allocation_message_hash(
+    contract_address: &HumanAddr,
+    user_addr: &HumanAddr,
+    stage: u128,
+    grant: u64,
+    total_amount: u128,
+    tge: u128,
+    start_at: u64,
+    cliff: u64,
+    duration: u64,
+) -> [u8; 32] {
+    let mut message = ALLOCATION_DOMAIN.to_vec();
+    push_length_prefixed(&mut message, contract_address.as_str().as_bytes());
+    push_length_prefixed(&mut message, user_addr.as_str().as_bytes());
+    message.extend_from_slice(&stage.to_be_bytes());
+    message.extend_from_slice(&grant.to_be_bytes());
+    message.extend_from_slice(&total_amount.to_be_bytes());
+    message.extend_from_slice(&tge.to_be_bytes());
+    message.extend_from_slice(&start_at.to_be_bytes());
+    message.extend_from_slice(&cliff.to_be_bytes());
+    message.extend_from_slice(&duration.to_be_bytes());
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(sha2::Sha256::digest(&message).as_slice());
+    hash
+}
+
+pub fn verify_allocation_signature<A: Api>(
+    api: &A,
+    algorithm: &SignatureAlgorithm,
+    public_key: &Binary,
+    message_hash: &[u8; 32],
+    signature: &Binary,
+) -> StdResult<()> {
+    if signature.len() != 64 {
+        return Err(StdError::generic_err(
+            "Invalid allocation signature length!",
+        ));
+    }
+
+    let is_valid = match algorithm {
+        SignatureAlgorithm::Secp256k1 => {
+            api.secp256k1_verify(message_hash, signature.as_slice(), public_key.as_slice())
+        }
+        SignatureAlgorithm::Ed25519 => {
+            api.ed25519_verify(message_hash, signature.as_slice(), public_key.as_slice())
+        }
+    }
+    .map_err(|err| StdError::generic_err(format!("Signature verification error: {}", err)))?;
+
+    if !is_valid {
+        return Err(StdError::generic_err("Signature verification failed!"));
+    }
+
+    Ok(())
+}
+
+/// Checks the length of an allocator public key before it is stored with a round
+pub fn validate_public_key(algorithm: &SignatureAlgorithm, public_key: &Binary) -> StdResult<()> {
+    let is_valid = match algorithm {
+        // Compressed or uncompressed SEC 2 encoding
+        SignatureAlgorithm::Secp256k1 => match public_key.as_slice() {
+            [0x02, ..] | [0x03, ..] => public_key.len() == 33,
+            [0x04, ..] => public_key.len() == 65,
+            _ => false,
+        },
+        SignatureAlgorithm::Ed25519 => public_key.len() == 32,
+    };
+
+    if !is_valid {
+        return Err(StdError::generic_err("Invalid allocator public key!"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_zebra::{SigningKey, VerificationKey};
+
+    #[test]
+    fn test_allocation_message_hash_is_unambiguous() {
+        let signing_key = SigningKey::from([7u8; 32]);
+        let verification_key = VerificationKey::from(&signing_key);
+        let contract = HumanAddr("vesting".to_string());
+        let alice = HumanAddr("alice".to_string());
+
+        // Written in decimal one after the other, these grants all read the same as the
+        // signed one, digits only move from one field to the next
+        let signed = allocation_message_hash(&contract, &alice, 1, 0, 100, 500, 0, 0, 86_400);
+        let mangled = [
+            allocation_message_hash(&contract, &alice, 1, 0, 1005, 0, 0, 0, 86_400),
+            allocation_message_hash(&contract, &alice, 11, 0, 0, 500, 0, 0, 86_400),
+            allocation_message_hash(&contract, &alice, 1, 0, 100, 500, 8, 0, 6_400),
+            allocation_message_hash(
+                &contract,
+                &HumanAddr("alice1".to_string()),
+                1,
+                0,
+                0,
+                500,
+                0,
+                0,
+                86_400,
+            ),
+        ];
+
+        let signature = signing_key.sign(&signed);
+        assert!(verification_key.verify(&signature, &signed).is_ok());
+        for hash in mangled.iter() {
+            assert_ne!(hash, &signed);
+            assert!(verification_key.verify(&signature, hash).is_err());
+        }
+    }
+}
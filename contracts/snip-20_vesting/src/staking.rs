@@ -0,0 +1,173 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{Bucket, ReadonlyBucket};
+
+use secret_toolkit::utils::{HandleCallback, Query};
+
+use crate::state::UserVestingStatsState;
+
+const PREFIX_STAKING: &[u8] = b"staking";
+
+/// Scale of `reward_per_token`, so rewards smaller than the staked amount still count
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+// Interface of the staking contract. Tokens are staked with a SNIP-20 `Send` carrying
+// `StakingReceiveMsg::Stake`, and come back with plain transfers to the vesting contract.
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingReceiveMsg {
+    Stake {},
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingHandleMsg {
+    /// Sends `amount` of the staked tokens back
+    Unstake {
+        amount: Uint128,
+    },
+    /// Sends every reward earned so far
+    ClaimRewards {},
+    SetViewingKey {
+        key: String,
+    },
+}
+
+impl HandleCallback for StakingHandleMsg {
+    const BLOCK_SIZE: usize = 256;
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingQueryMsg {
+    /// Rewards earned by `address` and not claimed yet
+    Rewards { address: HumanAddr, key: String },
+}
+
+impl Query for StakingQueryMsg {
+    const BLOCK_SIZE: usize = 256;
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StakingQueryAnswer {
+    Rewards { rewards: Uint128 },
+}
+
+// Stored types:
+
+/// Staking contract the escrow of a round is delegated to
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct StakingContract {
+    pub address: CanonicalAddr,
+    pub code_hash: String,
+}
+
+/// Reward accounting of a staking round.
+///
+/// Every harvest spreads the rewards over the staked tokens by raising `reward_per_token`,
+/// a position earns its unvested amount times the raise since it was last updated. The
+/// share of the escrow no position holds yet goes to `unassigned_rewards`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct StakingState {
+    /// Escrowed tokens held by the staking contract
+    pub total_staked: Uint128,
+    /// Rewards earned by a staked token, scaled by `REWARD_PRECISION`
+    pub reward_per_token: Uint128,
+    /// Rewards claimed from the staking contract
+    pub rewards_harvested: Uint128,
+    /// Rewards paid out to positions
+    pub rewards_paid: Uint128,
+    /// Unvested amount of the opened positions, the part of the escrow that earns for them
+    #[serde(default)]
+    pub positions_staked: Uint128,
+    /// Rewards earned by escrow no position held, until the owner sweeps them
+    #[serde(default)]
+    pub unassigned_rewards: Uint128,
+    /// Unassigned rewards the owner swept
+    #[serde(default)]
+    pub rewards_swept: Uint128,
+}
+
+impl StakingState {
+    /// Spreads harvested `rewards` over the staked tokens. What the positions don't earn,
+    /// because their escrow was not claimed yet or nothing is staked, is left unassigned.
+    pub fn distribute(&mut self, rewards: u128) -> StdResult<()> {
+        let overflow = || StdError::generic_err("Staking rewards overflow!");
+        self.rewards_harvested = Uint128::from(
+            self.rewards_harvested
+                .u128()
+                .checked_add(rewards)
+                .ok_or_else(overflow)?,
+        );
+
+        // Positions can hold more than what is staked once the escrow was withdrawn
+        let staked = self.total_staked.u128().max(self.positions_staked.u128());
+        let mut assigned = 0;
+        let scaled = rewards.checked_mul(REWARD_PRECISION).ok_or_else(overflow)?;
+        if let Some(raise) = scaled.checked_div(staked) {
+            self.reward_per_token = Uint128::from(self.reward_per_token.u128() + raise);
+            assigned = self.positions_staked.u128() * raise / REWARD_PRECISION;
+        }
+        self.unassigned_rewards =
+            Uint128::from(self.unassigned_rewards.u128() + rewards - assigned);
+        Ok(())
+    }
+
+    /// Updates `positions_staked` for a position whose unvested amount went from `before`
+    /// to `after`
+    pub fn move_position_stake(&mut self, before: u128, after: u128) {
+        self.positions_staked =
+            Uint128::from((self.positions_staked.u128() + after).saturating_sub(before));
+    }
+}
+
+/// Amount of the escrow staked for `position`, the TGE is paid when the position opens
+/// so only the linear amount is staked for it
+pub fn position_staked(position: &UserVestingStatsState) -> u128 {
+    position
+        .total_amount
+        .u128()
+        .saturating_sub(position.linear_claimed())
+}
+
+/// Adds what `position` earned since its last update to its `rewards`
+pub fn accrue_rewards(
+    position: &mut UserVestingStatsState,
+    reward_per_token: u128,
+) -> StdResult<()> {
+    let staked = position_staked(position);
+    let raise = reward_per_token.saturating_sub(position.reward_per_token_paid.u128());
+    let earned = staked
+        .checked_mul(raise)
+        .ok_or_else(|| StdError::generic_err("Staking rewards overflow!"))?
+        / REWARD_PRECISION;
+
+    position.rewards = Uint128::from(position.rewards.u128() + earned);
+    position.reward_per_token_paid = Uint128::from(reward_per_token);
+    Ok(())
+}
+
+// Storage functions:
+
+pub fn write_staking_state<S: Storage>(
+    storage: &mut S,
+    stage: u128,
+    state: &StakingState,
+) -> StdResult<()> {
+    Bucket::<S, StakingState>::new(PREFIX_STAKING, storage).save(&stage.to_be_bytes(), state)
+}
+
+/// Rounds that staked nothing yet have an empty state
+pub fn read_staking_state<S: ReadonlyStorage>(storage: &S, stage: u128) -> StdResult<StakingState> {
+    ReadonlyBucket::<S, StakingState>::new(PREFIX_STAKING, storage)
+        .may_load(&stage.to_be_bytes())
+        .map(Option::unwrap_or_default)
+}
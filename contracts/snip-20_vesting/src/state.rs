@@ -3,10 +3,10 @@ use serde::{Deserialize, Serialize};
 use std::any::type_name;
 
 
-use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128 };
+use cosmwasm_std::{Binary, CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128 };
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage, Bucket, ReadonlyBucket};
 
-use crate::{helpers::{set_bin_data, slice_to_u128, slice_to_u8}, constants::{ContractStatusLevel, status_level_to_u8, u8_to_status_level}};
+use crate::{staking::StakingContract, helpers::{get_bin_data, set_bin_data, slice_to_u128, slice_to_u8}, constants::{BPS_PRECISION, ContractStatusLevel, status_level_to_u8, u8_to_status_level}, utils::Duration, viewing_key::ViewingKey};
 
 pub static PREFIX_CONTRACT_OWNER_GRANTED: &[u8] = b"contract_owner_granted";
 pub static PREFIX_CONTRACT_OWNER: &[u8] = b"contract_owner";
@@ -15,33 +15,213 @@ pub static PREFIX_STAGE: &[u8] = b"stage";
 pub static PREFIX_CONFIG: &[u8] = b"config";
 pub static PREFIX_VESTING_ROUND: &[u8] = b"vesting_round";
 pub static USER_VESTING_STATS_PREFIX: &[u8] = b"user_vesting";
+pub static PREFIX_CLAIM_COUNT: &[u8] = b"claim_count";
+pub static PREFIX_VIEW_KEY: &[u8] = b"viewingkey";
+pub static PREFIX_CONTRACT_VIEWING_KEY: &[u8] = b"contract_viewing_key";
+pub static PREFIX_CONTRACT_ADDRESS: &[u8] = b"contract_address";
+pub static PREFIX_PRIVATE_AMOUNTS: &[u8] = b"private_amounts";
+pub static PREFIX_ROUND_METADATA: &[u8] = b"round_metadata";
+pub static PREFIX_ROUND_STATS: &[u8] = b"round_stats";
+
+/// Asset distributed by a vesting round
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VestingAsset {
+    /// SNIP-20 token, pulled from the distribution wallet unless the round is in custody
+    Snip20 {
+        token_address: CanonicalAddr,
+        token_code_hash: String,
+    },
+    /// Native coin (uscrt or an IBC denom) held by the contract
+    Native { denom: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureAlgorithm {
+    Secp256k1,
+    Ed25519,
+}
+
+/// Key that signs the grants of a signature round
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Allocator {
+    pub algorithm: SignatureAlgorithm,
+    pub public_key: Binary,
+}
+
+/// Share of every payout of a round sent to a fee recipient
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimFee {
+    /// Fee in basis points, at most `MAX_CLAIM_FEE_BPS`
+    pub rate_bps: u16,
+    pub recipient: CanonicalAddr,
+}
+
+impl ClaimFee {
+    /// Part of `amount` kept as fee, rounded down
+    pub fn fee_of(&self, amount: u128) -> u128 {
+        amount * u128::from(self.rate_bps) / BPS_PRECISION
+    }
+}
+
+/// How the grants of a round are proven when they are claimed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationMode {
+    /// Grants are leaves of a merkle tree, claims carry a proof
+    Merkle { merkle_root: String },
+    /// Grants are signed by the allocator, claims carry the signature
+    Signature { allocator: Allocator },
+    /// Grants are written on-chain by the admin with `AddAllocations`
+    Explicit { total_allocated: Uint128 },
+}
+
+/// Where the epochs of a round start
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EpochAnchor {
+    /// Epochs are multiples of the epoch length, daily epochs roll over at 00:00 UTC
+    Calendar,
+    /// Epochs of each grant start when its cliff ends
+    Grant,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundCategory {
+    Seed,
+    Private,
+    Public,
+    Team,
+    Advisors,
+    Airdrop,
+    Other,
+}
+
+/// Descriptive fields of a round, for explorers and auditors
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct RoundMetadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub category: Option<RoundCategory>,
+    /// Where the allocation list of the round is published
+    pub uri: Option<String>,
+    /// Hex encoded SHA-256 of the document at `uri`
+    pub content_hash: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct VestingRoundState {
     /// Owner If None set, contract is frozen
     pub distribution: CanonicalAddr,
-    pub token_code_hash: String,
-    pub token_address: CanonicalAddr,
+    pub asset: VestingAsset,
+    /// Claims are paid from tokens escrowed by the contract instead of the distribution wallet
+    pub custody: bool,
+    /// Sum of all allocations in the merkle tree, if declared at registration
+    pub total_allocation: Option<Uint128>,
+    /// Amount deposited into the contract for this round
+    pub total_funded: Uint128,
     pub total_claimed: Uint128,
-    pub merkle_root: String,
+    /// Amount taken out of the escrow with `EmergencyWithdraw`
+    pub total_withdrawn: Uint128,
+    pub allocation: AllocationMode,
+    /// Length of a vesting epoch. For `Height` rounds, `start_at`, `cliff` and `duration`
+    /// of the grants are block heights too
+    pub epoch: Duration,
+    pub epoch_anchor: EpochAnchor,
     pub created_at: u64,
-    pub is_paused: bool
+    /// Claims are turned down while the admin keeps the round paused
+    pub is_paused: bool,
+    /// Rounds without a fee pay the whole claim to the beneficiary
+    pub fee: Option<ClaimFee>,
+    /// Staking contract the escrow is delegated to, for custody rounds only
+    pub staking: Option<StakingContract>,
+    /// Rounds registered for `ImportPositions` turn claims down until the admin activates them
+    pub activated: bool
+}
+
+impl VestingRoundState {
+    /// Deposited tokens that have not been paid out or withdrawn yet
+    pub fn escrow_balance(&self) -> u128 {
+        self.total_funded
+            .u128()
+            .saturating_sub(self.total_claimed.u128())
+            .saturating_sub(self.total_withdrawn.u128())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct UserVestingStatsState {
     /// Owner If None set, contract is frozen.
     pub user: CanonicalAddr,
+    /// Index of the grant among the user's grants in the stage
+    #[serde(default)]
+    pub grant: u64,
     pub total_amount: Uint128,
     pub total_claimed: Uint128,
     pub vesting_duration: u64,
     pub cliff: u64,
     pub tge: Uint128,
+    /// Amount paid at token generation event, already deducted from `total_amount`
+    #[serde(default)]
+    pub tge_amount: Uint128,
     pub start_vesting_epoch: u64,
+    /// Hash of the leaf the position was opened with, later claims must present the same one
+    #[serde(default)]
+    pub leaf_hash: Option<Binary>,
+    /// Message claims are sent with to the owner's `Receive` hook when they don't give one
+    #[serde(default)]
+    pub claim_msg: Option<Binary>,
+    /// `reward_per_token` of the round when the staking rewards were last added up
+    #[serde(default)]
+    pub reward_per_token_paid: Uint128,
+    /// Staking rewards earned and not paid yet
+    #[serde(default)]
+    pub rewards: Uint128,
     // pub next_claim_epoch: u64,
     pub next_claim_epoch_index: u64
 }
 
+impl UserVestingStatsState {
+    /// Part of `total_claimed` paid from the linear schedule, `total_amount` excludes the TGE
+    pub fn linear_claimed(&self) -> u128 {
+        self.total_claimed.u128().saturating_sub(self.tge_amount.u128())
+    }
+}
+
+/// Claim activity of a round, kept up to date by `try_claim`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct RoundStatsState {
+    pub positions: u64,
+    pub fully_vested_positions: u64,
+    pub tge_paid: Uint128,
+    pub linear_paid: Uint128,
+    pub claim_count: u64,
+    pub first_claim_at: Option<u64>,
+    pub last_claim_at: Option<u64>,
+    /// Amount of the positions carried over with `ImportPositions`, TGE included
+    #[serde(default)]
+    pub imported_amount: Uint128,
+    /// Paid by the previous contract before the positions were imported, not part of
+    /// the round `total_claimed`
+    #[serde(default)]
+    pub imported_claimed: Uint128,
+    /// Claim fees sent to the fee recipient, part of `tge_paid` and `linear_paid`
+    #[serde(default)]
+    pub fees_paid: Uint128,
+}
+
+impl RoundStatsState {
+    pub fn record_claim(&mut self, tge_paid: u128, linear_paid: u128, time: u64) {
+        self.tge_paid = Uint128::from(self.tge_paid.u128() + tge_paid);
+        self.linear_paid = Uint128::from(self.linear_paid.u128() + linear_paid);
+        self.claim_count += 1;
+        self.first_claim_at.get_or_insert(time);
+        self.last_claim_at = Some(time);
+    }
+}
+
 // ============== VestingRound (Mutate ) ================= //
 
 pub struct VestingRound<'a, S: Storage> {
@@ -55,7 +235,7 @@ impl<'a, S: Storage> VestingRound<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyVestingRoundImpl<PrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyVestingRoundImpl<'_, PrefixedStorage<'_, S>> {
         ReadonlyVestingRoundImpl(&self.storage)
     }
 
@@ -85,7 +265,7 @@ impl<'a, S: ReadonlyStorage> ReadonlyVestingRound<'a, S> {
         self.as_readonly().config(current_stage)
     }
 
-    fn as_readonly(&self) -> ReadonlyVestingRoundImpl<ReadonlyPrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyVestingRoundImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
         ReadonlyVestingRoundImpl(&self.storage)
     }
 }
@@ -116,7 +296,7 @@ impl<'a, S: Storage> Config<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyConfigImpl<PrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyConfigImpl<'_, PrefixedStorage<'_, S>> {
         ReadonlyConfigImpl(&self.storage)
     }
 
@@ -136,6 +316,38 @@ impl<'a, S: Storage> Config<'a, S> {
         self.as_readonly().granted_contract_owner()
     }
 
+    pub fn claim_count(&self) -> u64 {
+        self.as_readonly().claim_count()
+    }
+
+    pub fn contract_viewing_key(&self) -> StdResult<ViewingKey> {
+        self.as_readonly().contract_viewing_key()
+    }
+
+    pub fn contract_address(&self) -> StdResult<CanonicalAddr> {
+        self.as_readonly().contract_address()
+    }
+
+    pub fn private_amounts(&self) -> bool {
+        self.as_readonly().private_amounts()
+    }
+
+    pub fn set_private_amounts(&mut self, private_amounts: bool) -> StdResult<()> {
+        set_bin_data(&mut self.storage, PREFIX_PRIVATE_AMOUNTS, &private_amounts)
+    }
+
+    pub fn set_contract_address(&mut self, address: &CanonicalAddr) -> StdResult<()> {
+        set_bin_data(&mut self.storage, PREFIX_CONTRACT_ADDRESS, &address)
+    }
+
+    pub fn set_contract_viewing_key(&mut self, key: &ViewingKey) -> StdResult<()> {
+        set_bin_data(&mut self.storage, PREFIX_CONTRACT_VIEWING_KEY, &key.0)
+    }
+
+    pub fn set_claim_count(&mut self, count: u64) -> StdResult<()> {
+        set_bin_data(&mut self.storage, PREFIX_CLAIM_COUNT, &count)
+    }
+
     pub fn new_stage(&mut self) -> u128 {
         let mut current_stage = self.storage.get(PREFIX_STAGE).map_or(
             0,
@@ -176,7 +388,7 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfig<'a, S> {
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyConfigImpl<ReadonlyPrefixedStorage<S>> {
+    fn as_readonly(&self) -> ReadonlyConfigImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
         ReadonlyConfigImpl(&self.storage)
     }
 
@@ -195,18 +407,33 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfig<'a, S> {
     pub fn contract_status(&self) -> ContractStatusLevel {
         self.as_readonly().contract_status()
     }
+
+    pub fn claim_count(&self) -> u64 {
+        self.as_readonly().claim_count()
+    }
+
+    pub fn contract_viewing_key(&self) -> StdResult<ViewingKey> {
+        self.as_readonly().contract_viewing_key()
+    }
+
+    pub fn contract_address(&self) -> StdResult<CanonicalAddr> {
+        self.as_readonly().contract_address()
+    }
+
+    pub fn private_amounts(&self) -> bool {
+        self.as_readonly().private_amounts()
+    }
 }
 
 struct ReadonlyConfigImpl<'a, S: ReadonlyStorage>(&'a S);
 
 impl<'a, S: ReadonlyStorage> ReadonlyConfigImpl<'a, S> {
+    /// Stage of the last registered round, 0 before the first one
     fn current_stage(&self) -> u128 {
-        let stage_bytes = self
-            .0
-            .get(PREFIX_STAGE)
-            .expect("no stage stored in config");
-        
-        slice_to_u128(&stage_bytes).unwrap()
+        self.0.get(PREFIX_STAGE).map_or(
+            0,
+            |stage_bytes| slice_to_u128(&stage_bytes).unwrap()
+        )
     }
 
     fn contract_status(&self) -> ContractStatusLevel {
@@ -236,10 +463,35 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfigImpl<'a, S> {
             bincode2::deserialize::<CanonicalAddr>(&contract_owner_bytes)
                 .map_err(|e| StdError::serialize_err(type_name::<CanonicalAddr>(), e))
     }
+
+    fn claim_count(&self) -> u64 {
+        get_bin_data(self.0, PREFIX_CLAIM_COUNT).unwrap_or_default()
+    }
+
+    fn contract_viewing_key(&self) -> StdResult<ViewingKey> {
+        get_bin_data(self.0, PREFIX_CONTRACT_VIEWING_KEY).map(ViewingKey)
+    }
+
+    fn contract_address(&self) -> StdResult<CanonicalAddr> {
+        get_bin_data(self.0, PREFIX_CONTRACT_ADDRESS)
+    }
+
+    fn private_amounts(&self) -> bool {
+        get_bin_data(self.0, PREFIX_PRIVATE_AMOUNTS).unwrap_or_default()
+    }
 }
 
 // ============== User Vesting (Mutate ) ================= //
 
+/// First grants keep the key positions had before grants were indexed
+fn position_key(stage: u128, grant: u64) -> Vec<u8> {
+    let mut key = stage.to_be_bytes().to_vec();
+    if grant > 0 {
+        key.extend_from_slice(&grant.to_be_bytes());
+    }
+    key
+}
+
 pub fn write_user_vesting_stats<S: Storage>(
     storage: &mut S,
     vesting_stats: &UserVestingStatsState,
@@ -250,18 +502,72 @@ pub fn write_user_vesting_stats<S: Storage>(
         storage,
     );
 
-    user_vesting_store.save(&stage.to_be_bytes(), vesting_stats)
+    user_vesting_store.save(&position_key(stage, vesting_stats.grant), vesting_stats)
 }
 
 pub fn read_user_vesting_stats<S: Storage>(
     storage: &S,
     user: &CanonicalAddr,
-    stage: u128
+    stage: u128,
+    grant: u64
 ) -> StdResult<Option<UserVestingStatsState>> {
     let user_vesting_store = ReadonlyBucket::<S, UserVestingStatsState>::multilevel(
         &[USER_VESTING_STATS_PREFIX, user.as_slice()],
         storage,
     );
 
-   user_vesting_store.may_load(&stage.to_be_bytes())
+   user_vesting_store.may_load(&position_key(stage, grant))
+}
+
+// ============== Round Metadata ================= //
+
+// Kept apart from `VestingRoundState` so claims don't load the descriptions
+pub fn write_round_metadata<S: Storage>(
+    storage: &mut S,
+    stage: u128,
+    metadata: &RoundMetadata
+) -> StdResult<()> {
+    Bucket::<S, RoundMetadata>::new(PREFIX_ROUND_METADATA, storage)
+        .save(&stage.to_be_bytes(), metadata)
+}
+
+pub fn read_round_metadata<S: ReadonlyStorage>(
+    storage: &S,
+    stage: u128
+) -> StdResult<Option<RoundMetadata>> {
+    ReadonlyBucket::<S, RoundMetadata>::new(PREFIX_ROUND_METADATA, storage)
+        .may_load(&stage.to_be_bytes())
+}
+
+// ============== Round Stats ================= //
+
+pub fn write_round_stats<S: Storage>(
+    storage: &mut S,
+    stage: u128,
+    stats: &RoundStatsState
+) -> StdResult<()> {
+    Bucket::<S, RoundStatsState>::new(PREFIX_ROUND_STATS, storage)
+        .save(&stage.to_be_bytes(), stats)
+}
+
+/// Rounds nobody claimed from yet have empty stats
+pub fn read_round_stats<S: ReadonlyStorage>(
+    storage: &S,
+    stage: u128
+) -> StdResult<RoundStatsState> {
+    ReadonlyBucket::<S, RoundStatsState>::new(PREFIX_ROUND_STATS, storage)
+        .may_load(&stage.to_be_bytes())
+        .map(Option::unwrap_or_default)
+}
+
+// ============== Viewing Keys ================= //
+
+pub fn write_viewing_key<S: Storage>(store: &mut S, owner: &CanonicalAddr, key: &ViewingKey) {
+    let mut viewing_key_store = PrefixedStorage::new(PREFIX_VIEW_KEY, store);
+    viewing_key_store.set(owner.as_slice(), &key.to_hashed());
+}
+
+pub fn read_viewing_key<S: Storage>(store: &S, owner: &CanonicalAddr) -> Option<Vec<u8>> {
+    let viewing_key_store = ReadonlyPrefixedStorage::new(PREFIX_VIEW_KEY, store);
+    viewing_key_store.get(owner.as_slice())
 }
@@ -2,8 +2,36 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{BlockInfo, StdError, StdResult};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
 use std::fmt;
 use std::ops::{Add, Mul};
+use subtle::ConstantTimeEq;
+
+use crate::constants::MAX_PAGE_SIZE;
+use crate::viewing_key::VIEWING_KEY_SIZE;
+
+pub fn ct_slice_compare(s1: &[u8], s2: &[u8]) -> bool {
+    bool::from(s1.ct_eq(s2))
+}
+
+pub fn create_hashed_password(s1: &str) -> [u8; VIEWING_KEY_SIZE] {
+    Sha256::digest(s1.as_bytes())
+        .as_slice()
+        .try_into()
+        .expect("Wrong password length")
+}
+
+/// Number of entries to skip and to take for the page `page` of `page_size` entries,
+/// `page_size` being capped to `MAX_PAGE_SIZE`
+pub fn page_bounds(page: u32, page_size: u32) -> StdResult<(usize, usize)> {
+    let page_size = page_size.min(MAX_PAGE_SIZE);
+    let skip = page
+        .checked_mul(page_size)
+        .ok_or_else(|| StdError::generic_err("Page out of range!"))?;
+
+    Ok((skip as usize, page_size as usize))
+}
 
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
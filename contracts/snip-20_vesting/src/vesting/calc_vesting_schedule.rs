@@ -1,27 +1,227 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{BlockInfo, StdError, StdResult};
 
-use crate::constants::{ONE_DAY_IN_SECONDS};
+use crate::constants::TGE_PRECISION;
+use crate::state::EpochAnchor;
+use crate::utils::Duration;
 
+/// Length of a vesting epoch, in seconds for `Time` rounds and in blocks for `Height` rounds
+pub fn epoch_length(epoch: &Duration) -> u64 {
+    match epoch {
+        Duration::Time(seconds) => *seconds,
+        Duration::Height(blocks) => *blocks,
+    }
+}
+
+/// Current point of the round schedule, in the unit of its `start_at`, `cliff` and `duration`
+pub fn schedule_now(epoch: &Duration, block: &BlockInfo) -> u64 {
+    match epoch {
+        Duration::Time(_) => block.time,
+        Duration::Height(_) => block.height,
+    }
+}
+
+/// Amount paid at token generation event, `tge` is expressed in `TGE_PRECISION` units
+pub fn calc_tge_amount(total_amount: u128, tge: u128) -> StdResult<u128> {
+    if tge > TGE_PRECISION {
+        return Err(StdError::generic_err(format!("TGE can be at most {}!", TGE_PRECISION)));
+    }
+
+    total_amount
+        .checked_mul(tge)
+        .map(|amount| amount / TGE_PRECISION)
+        .ok_or_else(|| StdError::generic_err("TGE amount overflow!"))
+}
+
+/// Index of the epoch `now` falls in.
+///
+/// `Calendar` epochs are counted from zero, so daily epochs roll over at 00:00 UTC, while
+/// `Grant` epochs are counted from the end of the grant's cliff, where epoch 0 begins.
+pub fn epoch_index(now: u64, start_at: u64, cliff: u64, epoch_length: u64, anchor: &EpochAnchor) -> u64 {
+    match anchor {
+        EpochAnchor::Calendar => now.checked_div(epoch_length).unwrap(),
+        EpochAnchor::Grant => {
+            let cliff_end = start_at.checked_add(cliff).unwrap();
+            now.saturating_sub(cliff_end).checked_div(epoch_length).unwrap()
+        }
+    }
+}
+
+/// Point of the schedule at which the epoch `epoch_index` begins
+pub fn epoch_start(epoch_index: u64, start_at: u64, cliff: u64, epoch_length: u64, anchor: &EpochAnchor) -> u64 {
+    let offset = epoch_index.checked_mul(epoch_length).unwrap();
+    match anchor {
+        EpochAnchor::Calendar => offset,
+        EpochAnchor::Grant => start_at.checked_add(cliff).unwrap().checked_add(offset).unwrap(),
+    }
+}
+
+/// Index of the first epoch that can be claimed, the one the cliff ends in.
+///
+/// A position's `next_claim_epoch_index` starts there and moves past the epoch of each claim,
+/// so for `Grant` rounds it is relative to the cliff end instead of an absolute day number.
+pub fn first_claim_epoch_index(start_at: u64, cliff: u64, epoch_length: u64, anchor: &EpochAnchor) -> u64 {
+    epoch_index(start_at.checked_add(cliff).unwrap(), start_at, cliff, epoch_length, anchor)
+}
+
+/// Linear amount vested once `epoch_index` is reached, capped at `total_amount`.
+///
+/// The amount is computed from the start of the schedule rather than summed per claim, so
+/// truncation never accumulates and the last epoch of the schedule vests exactly `total_amount`.
+pub fn calc_vested_amount(
+    epoch_index: u64,
+    first_epoch_index: u64,
+    duration: u64,
+    total_amount: u128,
+    epoch_length: u64
+) -> Result<u128, StdError> {
+    if epoch_index < first_epoch_index {
+        return Ok(0);
+    }
+
+    let duration_in_epochs = duration.checked_div(epoch_length).unwrap();
+    if duration_in_epochs.eq(&0) {
+        return Ok(total_amount);
+    }
+
+    // Epochs past the end of the schedule don't vest anything more
+    let passed_epochs = (epoch_index - first_epoch_index + 1).min(duration_in_epochs);
+    if passed_epochs == duration_in_epochs {
+        return Ok(total_amount);
+    }
+
+    total_amount
+        .checked_mul(passed_epochs as u128)
+        .map(|amount| amount / duration_in_epochs as u128)
+        .ok_or_else(|| StdError::generic_err("Vesting amount overflow!"))
+}
+
+/// Returns the amount to pay for a claim at `current_epoch_index` given what the position
+/// already received from the linear schedule, and the next epoch index it can claim.
 pub fn calc_current_vesting_amount(
     current_epoch_index: u64,
-    next_claim_epoch_index: u64,
+    first_epoch_index: u64,
     duration: u64,
-    total_amount: u128
+    total_amount: u128,
+    claimed_amount: u128,
+    epoch_length: u64
 ) -> Result<(u128, u64), StdError> {
-    let passed_days;
-    let total_claim_amount;
+    let vested_amount = calc_vested_amount(current_epoch_index, first_epoch_index, duration, total_amount, epoch_length)?;
+    let claim_amount = vested_amount.saturating_sub(claimed_amount);
+
+    Ok((claim_amount, current_epoch_index.checked_add(1u64).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ONE_DAY_IN_SECONDS;
+    use crate::rand::Prng;
+
+    fn rand_u64(rng: &mut Prng, max: u64) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&rng.rand_bytes()[..8]);
+        match max.checked_add(1) {
+            Some(modulus) => u64::from_be_bytes(bytes) % modulus,
+            None => u64::from_be_bytes(bytes),
+        }
+    }
+
+    #[test]
+    fn test_claimed_sum_equals_allocation() {
+        let mut rng = Prng::new(b"vesting schedule", b"claimed sum");
+
+        for _ in 0..2_000 {
+            let total_amount = rand_u64(&mut rng, u64::MAX) as u128;
+            let tge = rand_u64(&mut rng, TGE_PRECISION as u64) as u128;
+            let start_at = 1_600_000_000 + rand_u64(&mut rng, 365 * ONE_DAY_IN_SECONDS);
+            let cliff = rand_u64(&mut rng, 90 * ONE_DAY_IN_SECONDS);
+            let duration = rand_u64(&mut rng, 4 * 365 * ONE_DAY_IN_SECONDS);
+
+            let tge_amount = calc_tge_amount(total_amount, tge).unwrap();
+            let linear_amount = total_amount - tge_amount;
+            let first_epoch_index =
+                first_claim_epoch_index(start_at, cliff, ONE_DAY_IN_SECONDS, &EpochAnchor::Calendar);
+            let last_epoch_index = first_epoch_index + (duration / ONE_DAY_IN_SECONDS).max(1) - 1;
 
-    let mut latest_next_claim_epoch_index = next_claim_epoch_index;
-    let duration_in_days = duration.checked_div(ONE_DAY_IN_SECONDS).unwrap();
+            // Claim at random epochs, possibly long after the end of the schedule
+            let mut claimed = 0u128;
+            let mut epoch_index = first_epoch_index;
+            loop {
+                let (amount, next_claim_epoch_index) = calc_current_vesting_amount(
+                    epoch_index,
+                    first_epoch_index,
+                    duration,
+                    linear_amount,
+                    claimed,
+                    ONE_DAY_IN_SECONDS,
+                )
+                .unwrap();
+                claimed += amount;
+                assert!(claimed <= linear_amount);
 
-    if duration_in_days.eq(&0) {
-        total_claim_amount = total_amount;
-    } else {
-        passed_days = current_epoch_index.checked_sub(next_claim_epoch_index).unwrap().checked_add(1u64).unwrap();
-    
-        total_claim_amount = total_amount.checked_mul(passed_days as u128).unwrap().checked_div(duration_in_days as u128).unwrap();
-        latest_next_claim_epoch_index = current_epoch_index.checked_add(1u64).unwrap();
+                if epoch_index >= last_epoch_index {
+                    break;
+                }
+                epoch_index = next_claim_epoch_index + rand_u64(&mut rng, 60);
+            }
+
+            assert_eq!(tge_amount + claimed, total_amount);
+        }
+    }
+
+    #[test]
+    fn test_vested_amount_is_clamped() {
+        let mut rng = Prng::new(b"vesting schedule", b"clamped");
+
+        for _ in 0..2_000 {
+            let total_amount = rand_u64(&mut rng, u64::MAX) as u128;
+            let first_epoch_index = rand_u64(&mut rng, 100_000);
+            let duration = rand_u64(&mut rng, 4 * 365 * ONE_DAY_IN_SECONDS);
+            let days = (duration / ONE_DAY_IN_SECONDS).max(1);
+
+            let mut previous = 0u128;
+            for offset in 0..days + 5 {
+                let vested =
+                    calc_vested_amount(first_epoch_index + offset, first_epoch_index, duration, total_amount, ONE_DAY_IN_SECONDS)
+                        .unwrap();
+                assert!(vested >= previous);
+                assert!(vested <= total_amount);
+                previous = vested;
+            }
+            assert_eq!(previous, total_amount);
+        }
     }
 
-    Ok((total_claim_amount, latest_next_claim_epoch_index))
-}
\ No newline at end of file
+    #[test]
+    fn test_epoch_index_around_day_boundaries() {
+        let day = ONE_DAY_IN_SECONDS;
+        // 2020-09-13 23:00:00 UTC, one hour before midnight
+        let start_at = 1_600_038_000;
+        let midnight = start_at + 3_600;
+        assert_eq!(midnight % day, 0);
+
+        let calendar = |now| epoch_index(now, start_at, 0, day, &EpochAnchor::Calendar);
+        let first = first_claim_epoch_index(start_at, 0, day, &EpochAnchor::Calendar);
+        assert_eq!(calendar(start_at + 1), first);
+        assert_eq!(calendar(midnight - 1), first);
+        assert_eq!(calendar(midnight), first + 1);
+        assert_eq!(epoch_start(first + 1, start_at, 0, day, &EpochAnchor::Calendar), midnight);
+
+        let grant = |now| epoch_index(now, start_at, 0, day, &EpochAnchor::Grant);
+        assert_eq!(first_claim_epoch_index(start_at, 0, day, &EpochAnchor::Grant), 0);
+        assert_eq!(grant(start_at + 1), 0);
+        assert_eq!(grant(midnight), 0);
+        assert_eq!(grant(start_at + day - 1), 0);
+        assert_eq!(grant(start_at + day), 1);
+        assert_eq!(epoch_start(1, start_at, 0, day, &EpochAnchor::Grant), start_at + day);
+
+        // The cliff moves the anchor of grant epochs, not calendar ones
+        let cliff = 2 * 3_600;
+        assert_eq!(epoch_index(start_at + cliff + day - 1, start_at, cliff, day, &EpochAnchor::Grant), 0);
+        assert_eq!(epoch_index(start_at + cliff + day, start_at, cliff, day, &EpochAnchor::Grant), 1);
+        assert_eq!(
+            first_claim_epoch_index(start_at, cliff, day, &EpochAnchor::Calendar),
+            midnight / day
+        );
+    }
+}
@@ -1 +1,2 @@
-pub mod calc_vesting_schedule;
\ No newline at end of file
+pub mod calc_vesting_schedule;
+pub mod unlock_schedule;
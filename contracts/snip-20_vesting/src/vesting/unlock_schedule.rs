@@ -0,0 +1,127 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{StdError, StdResult, Uint128};
+
+use crate::state::EpochAnchor;
+use crate::utils::Duration;
+use crate::vesting::calc_vesting_schedule::{
+    calc_vested_amount, epoch_length, epoch_start, first_claim_epoch_index,
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlockEventKind {
+    Tge,
+    CliffEnd,
+    Epoch,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct UnlockEvent {
+    pub kind: UnlockEventKind,
+    /// Epoch index of `Epoch` events, counted from the cliff end for `Grant` anchored rounds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epoch_index: Option<u64>,
+    /// Earliest block time, or block height for `Height` rounds, at which a claim includes this event
+    pub unlock_at: u64,
+    pub amount: Uint128,
+    /// Total unlocked by this event, TGE included
+    pub cumulative_amount: Uint128,
+}
+
+/// Lists the unlocks of a grant, in the order `try_claim` pays them: the cliff end and the
+/// TGE, then at most `limit` epochs following the epoch index `start_after`. The cliff end
+/// and the TGE are only listed on the first page. Returns the events and the number of
+/// epochs of the schedule.
+///
+/// Cumulative amounts come from `calc_vested_amount`, which `try_claim` pays the difference of,
+/// so the preview doesn't depend on when the previous claims happened.
+#[allow(clippy::too_many_arguments)]
+pub fn unlock_events(
+    tge_amount: u128,
+    linear_amount: u128,
+    start_at: u64,
+    cliff: u64,
+    duration: u64,
+    epoch: &Duration,
+    anchor: &EpochAnchor,
+    start_after: Option<u64>,
+    limit: u32,
+) -> StdResult<(Vec<UnlockEvent>, u64)> {
+    let mut events = vec![];
+    let epoch_length = epoch_length(epoch);
+
+    // The whole schedule, and the first claim after it, must be representable
+    let invalid_schedule = || StdError::generic_err("Invalid vesting schedule!");
+    let cliff_end = start_at.checked_add(cliff).ok_or_else(invalid_schedule)?;
+    cliff_end
+        .checked_add(duration)
+        .and_then(|end| end.checked_add(1))
+        .ok_or_else(invalid_schedule)?;
+
+    if start_after.is_none() && cliff > 0 {
+        events.push(UnlockEvent {
+            kind: UnlockEventKind::CliffEnd,
+            epoch_index: None,
+            unlock_at: cliff_end,
+            amount: Uint128::zero(),
+            cumulative_amount: Uint128::zero(),
+        });
+    }
+
+    // `try_claim` turns claims down until the cliff is over when the grant has a TGE,
+    // so the TGE is paid together with the first epoch
+    if start_after.is_none() && tge_amount > 0 {
+        events.push(UnlockEvent {
+            kind: UnlockEventKind::Tge,
+            epoch_index: None,
+            unlock_at: cliff_end + 1,
+            amount: Uint128::from(tge_amount),
+            cumulative_amount: Uint128::from(tge_amount),
+        });
+    }
+
+    // Claims are accepted strictly after the cliff, starting from the epoch it ends in
+    let first_epoch_index = first_claim_epoch_index(start_at, cliff, epoch_length, anchor);
+    let epochs = (duration / epoch_length).max(1);
+    let end_epoch_index = first_epoch_index
+        .checked_add(epochs)
+        .ok_or_else(invalid_schedule)?;
+    let page_start = start_after.map_or(first_epoch_index, |index| {
+        index.saturating_add(1).max(first_epoch_index)
+    });
+    let mut unlocked = match page_start.checked_sub(1) {
+        Some(previous) => calc_vested_amount(
+            previous,
+            first_epoch_index,
+            duration,
+            linear_amount,
+            epoch_length,
+        )?,
+        None => 0,
+    };
+
+    for epoch_index in (page_start..end_epoch_index).take(limit as usize) {
+        let vested = calc_vested_amount(
+            epoch_index,
+            first_epoch_index,
+            duration,
+            linear_amount,
+            epoch_length,
+        )?;
+
+        events.push(UnlockEvent {
+            kind: UnlockEventKind::Epoch,
+            epoch_index: Some(epoch_index),
+            unlock_at: epoch_start(epoch_index, start_at, cliff, epoch_length, anchor)
+                .max(cliff_end + 1),
+            amount: Uint128::from(vested - unlocked),
+            cumulative_amount: Uint128::from(tge_amount + vested),
+        });
+        unlocked = vested;
+    }
+
+    Ok((events, epochs))
+}
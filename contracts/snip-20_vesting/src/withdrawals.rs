@@ -0,0 +1,108 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    Api, BlockInfo, CanonicalAddr, HumanAddr, ReadonlyStorage, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+use crate::utils::page_bounds;
+
+const PREFIX_WITHDRAWALS: &[u8] = b"withdrawals";
+
+// Withdrawals are kept per stage, next to the round's `total_withdrawn`, so every
+// token that left the round outside of a claim can be accounted for.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct WithdrawalTx {
+    pub id: u64,
+    pub stage: Uint128,
+    /// Token contract address or native denom that was withdrawn
+    pub token: String,
+    pub amount: Uint128,
+    pub recipient: HumanAddr,
+    pub block_time: u64,
+    pub block_height: u64,
+}
+
+// Stored types:
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+struct StoredWithdrawalTx {
+    id: u64,
+    token: String,
+    amount: u128,
+    recipient: CanonicalAddr,
+    block_time: u64,
+    block_height: u64,
+}
+
+impl StoredWithdrawalTx {
+    fn into_humanized<A: Api>(self, api: &A, stage: u128) -> StdResult<WithdrawalTx> {
+        Ok(WithdrawalTx {
+            id: self.id,
+            stage: Uint128::from(stage),
+            token: self.token,
+            amount: Uint128::from(self.amount),
+            recipient: api.human_address(&self.recipient)?,
+            block_time: self.block_time,
+            block_height: self.block_height,
+        })
+    }
+}
+
+// Storage functions:
+
+pub fn store_withdrawal<S: Storage>(
+    store: &mut S,
+    stage: u128,
+    token: String,
+    amount: u128,
+    recipient: &CanonicalAddr,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_WITHDRAWALS, &stage.to_be_bytes()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+
+    let withdrawal = StoredWithdrawalTx {
+        id: store.len() as u64 + 1,
+        token,
+        amount,
+        recipient: recipient.clone(),
+        block_time: block.time,
+        block_height: block.height,
+    };
+    store.push(&withdrawal)
+}
+
+pub fn get_withdrawals<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    stage: u128,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<WithdrawalTx>, u64)> {
+    let store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_WITHDRAWALS, &stage.to_be_bytes()], storage);
+
+    // Rounds that never had an emergency withdrawal have no store yet
+    let store = AppendStore::<StoredWithdrawalTx, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let (skip, take) = page_bounds(page, page_size)?;
+    let withdrawals: StdResult<Vec<WithdrawalTx>> = store
+        .iter()
+        .rev()
+        .skip(skip)
+        .take(take)
+        .map(|tx| tx.and_then(|tx| tx.into_humanized(api, stage)))
+        .collect();
+    withdrawals.map(|withdrawals| (withdrawals, store.len() as u64))
+}
@@ -16,3 +16,6 @@
 //!          //...
 //!      });
 //! 4. Anywhere you see query(&deps, ...) you must replace it with query(&mut deps, ...)
+
+// The steps above keep their snippets indented under the list items
+#![allow(clippy::doc_overindented_list_items)]
@@ -17,7 +17,8 @@ async function run () {
   const resp = await contract.instantiate(
     {
       "owner": contract_owner.account.address,
-      "contract_status": 0
+      "contract_status": 0,
+      "prng_seed": Buffer.from('Vesting random seed').toString('base64')
     },
     "Instantiate config",
     contract_owner
@@ -56,7 +56,8 @@ describe("vesting", () => {
     await contract.instantiate(
       {
         "owner": contract_owner.account.address,
-        "contract_status": 0
+        "contract_status": 0,
+        "prng_seed": Buffer.from('Vesting random seed').toString('base64')
       },
       "Instantiate config",
       contract_owner